@@ -3,12 +3,16 @@ use std::io::{self, Write};
 use std::rc::Rc;
 
 use collapse_messenger::blob::BlobBody;
-use collapse_messenger::content::{Content, StatusEvent, Message};
-use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::content::{Content, StatusEvent};
+use collapse_messenger::message_store::MessageStore;
+use collapse_messenger::node::{NodeMessenger, Review};
 use collapse_messenger::phi::Evidence;
 use collapse_messenger::store;
 use collapse_messenger::transport_mem::MemoryTransport;
-use collapse_messenger::types::{Digest, PubKey, Timestamp, zero_digest};
+use collapse_messenger::types::{Digest, PubKey, zero_digest};
+
+/// Events per peer shown by the `rep` command.
+const REP_RECENT_EVENTS: usize = 5;
 
 struct Net {
     a: NodeMessenger,
@@ -20,9 +24,18 @@ impl Net {
     fn new() -> Self {
         let bus = Rc::new(RefCell::new(MemoryTransport::new()));
 
-        let mut a = NodeMessenger::new(PubKey("A".to_string()), bus.clone());
-        let mut b = NodeMessenger::new(PubKey("B".to_string()), bus.clone());
-        let mut c = NodeMessenger::new(PubKey("C".to_string()), bus.clone());
+        // Each node keeps its identity, inbox, reputation and CAS under
+        // .collapse/<name>, so a restarted REPL resumes where it left off.
+        // Blob bytes travel between nodes through the blob
+        // request/response frames on poll.
+        let node = |name: &str| {
+            let dir = format!(".collapse/{}", name);
+            NodeMessenger::open(&dir, Box::new(bus.clone()))
+                .unwrap_or_else(|e| panic!("cannot open node state in {}: {}", dir, e))
+        };
+        let mut a = node("A");
+        let mut b = node("B");
+        let mut c = node("C");
 
         // Fully connect A, B, C as peers.
         a.add_peer(b.id.clone());
@@ -56,7 +69,7 @@ impl Net {
     }
 
     fn last_digest(&self, who: &str) -> Option<Digest> {
-        let inbox: &Vec<Message> = match who {
+        let inbox: &MessageStore = match who {
             "A" => &self.a.inbox,
             "B" => &self.b.inbox,
             "C" => &self.c.inbox,
@@ -90,7 +103,9 @@ impl Net {
             }
         };
 
-        n.send(parent, Evidence::DraftText { raw: body.to_string() });
+        if let Err(e) = n.send(parent, Evidence::DraftText { raw: body.to_string() }) {
+            eprintln!("send failed: {}", e);
+        }
     }
 
     fn cmd_send_retina(&mut self, from: &str, parent_sel: &str) {
@@ -114,7 +129,7 @@ impl Net {
         let basis_cfg = (8_u32, 8_u32);
         let cert_seed: u64 = 0;
 
-        n.send(
+        let sent = n.send(
             parent,
             Evidence::RawRetinaCapture {
                 samples,
@@ -124,6 +139,9 @@ impl Net {
                 cert_seed,
             },
         );
+        if let Err(e) = sent {
+            eprintln!("send failed: {}", e);
+        }
     }
 
     fn cmd_send_blob(&mut self, from: &str, parent_sel: &str, path: &str, mime: &str) {
@@ -140,21 +158,16 @@ impl Net {
             }
         };
 
-        let bytes = match std::fs::read(path) {
-            Ok(b) => b,
-            Err(e) => {
-                eprintln!("read {} failed: {}", path, e);
-                return;
-            }
-        };
-
-        n.send(
+        let sent = n.send(
             parent,
-            Evidence::Blob {
-                bytes,
+            Evidence::BlobFile {
+                path: path.into(),
                 mime: mime.to_string(),
             },
         );
+        if let Err(e) = sent {
+            eprintln!("read {} failed: {}", path, e);
+        }
     }
 
     /// For now, send_blob_to uses the same broadcast semantics as send_blob.
@@ -193,7 +206,21 @@ impl Net {
                 return;
             }
         };
-        n.poll();
+        for receipt in n.poll() {
+            match receipt {
+                Ok(acc) => {
+                    println!(
+                        "accepted {} (+{} released)",
+                        store::digest_to_hex(&acc.digest),
+                        acc.released.len()
+                    );
+                    for r in acc.refused {
+                        println!("⚠️ rejected {}", r);
+                    }
+                }
+                Err(r) => println!("⚠️ rejected {}", r),
+            }
+        }
     }
 
     fn cmd_inbox(&self, who: &str) {
@@ -238,23 +265,159 @@ impl Net {
                         mime, len, object_digest
                     );
                 }
+                Content::Attestation(a) => {
+                    let scores: Vec<String> = a
+                        .scores
+                        .iter()
+                        .map(|(pk, s)| format!("{}={:.3}", self.name_of(pk), s))
+                        .collect();
+                    println!(" ATTESTATION: {}", scores.join(" "));
+                }
+            }
+        }
+    }
+
+    /// Node name (A/B/C) for a key, or its hex for strangers.
+    fn name_of(&self, pk: &PubKey) -> String {
+        for (name, n) in [("A", &self.a), ("B", &self.b), ("C", &self.c)] {
+            if n.id == *pk {
+                return name.to_string();
             }
         }
+        pk.to_string()
     }
 
+    /// CLI: rep WHO
+    /// every peer WHO has scored, with its most recent events
     fn cmd_rep(&self, who: &str) {
-        let _n = match self.node_ref(who) {
+        let n = match self.node_ref(who) {
+            Some(n) => n,
+            None => {
+                eprintln!("no such node {}", who);
+                return;
+            }
+        };
+
+        let mut scores: Vec<(String, PubKey, f64)> = n
+            .rep
+            .scores()
+            .into_iter()
+            .map(|(pk, s)| (self.name_of(&pk), pk, s))
+            .collect();
+        scores.sort_by(|a, b| a.0.cmp(&b.0));
+        if scores.is_empty() {
+            println!("rep {}: no peers scored yet (threshold {})", who, n.rep.admit_threshold());
+            return;
+        }
+
+        println!("rep {} (admit threshold {}):", who, n.rep.admit_threshold());
+        for (name, pk, score) in scores {
+            let local = n.rep.local(&pk);
+            if (local - score).abs() > 5e-4 {
+                println!("  {} = {:.3} (first-hand {:.3})", name, score, local);
+            } else {
+                println!("  {} = {:.3}", name, score);
+            }
+            let history = n.rep.history(&pk);
+            let recent = &history[history.len().saturating_sub(REP_RECENT_EVENTS)..];
+            for e in recent {
+                let digest = e.digest.as_ref().map(store::digest_to_hex).unwrap_or_default();
+                println!(
+                    "    {:+.3} {:?} at {} {}",
+                    e.delta,
+                    e.reason,
+                    e.at.0,
+                    &digest[..digest.len().min(16)]
+                );
+            }
+        }
+    }
+
+    /// CLI: quarantine WHO
+    /// messages WHO is holding back from low-reputation senders
+    fn cmd_quarantine(&self, who: &str) {
+        let n = match self.node_ref(who) {
+            Some(n) => n,
+            None => {
+                eprintln!("no such node {}", who);
+                return;
+            }
+        };
+        if n.quarantine.is_empty() {
+            println!("quarantine {}: empty", who);
+            return;
+        }
+        for (i, q) in n.quarantine.iter().enumerate() {
+            let digest = store::digest_to_hex(&q.msg.digest);
+            println!(
+                "#{} {} from {} (score {:.3} on arrival) {:?}",
+                i,
+                &digest[..16],
+                self.name_of(&q.msg.sender),
+                q.score,
+                q.msg.content.kind()
+            );
+        }
+    }
+
+    /// CLI: release WHO INDEX [restore] / discard WHO INDEX
+    fn cmd_review(&mut self, who: &str, idx: usize, review: Review) {
+        let n = match self.node_mut(who) {
+            Some(n) => n,
+            None => {
+                eprintln!("no such node {}", who);
+                return;
+            }
+        };
+        let digest = match n.quarantine.get(idx) {
+            Some(q) => q.msg.digest.clone(),
+            None => {
+                eprintln!("no such quarantine index {}", idx);
+                return;
+            }
+        };
+        match review {
+            Review::Release { restore } => {
+                if let Some(Ok(acc)) = n.release(&digest, restore) {
+                    println!(
+                        "released {} (+{} released)",
+                        store::digest_to_hex(&acc.digest),
+                        acc.released.len()
+                    );
+                }
+            }
+            Review::Discard => {
+                if let Some(r) = n.discard(&digest) {
+                    println!("discarded {}", r);
+                }
+            }
+            Review::Keep => {}
+        }
+    }
+
+    /// CLI: attest WHO
+    /// publish WHO's first-hand scores of its peers
+    fn cmd_attest(&mut self, who: &str) {
+        let n = match self.node_mut(who) {
             Some(n) => n,
             None => {
                 eprintln!("no such node {}", who);
                 return;
             }
         };
-        // Stub: we avoid touching ReputationBook internals here.
-        println!("rep {}: <not implemented in REPL>", who);
+        let digest = n.publish_attestation();
+        println!("attestation_digest = {:?}", digest);
     }
 
-    fn cmd_fuse(&mut self, who: &str) {
+    /// CLI: fuse WHO root|last
+    /// fuse the retina captures WHO has under that parent and publish
+    /// the fused scene as a reply to it
+    fn cmd_fuse(&mut self, who: &str, parent_sel: &str) {
+        let parent = match self.parent_for(who, parent_sel) {
+            Some(d) => d,
+            None => return,
+        };
+
         let n = match self.node_mut(who) {
             Some(n) => n,
             None => {
@@ -262,9 +425,20 @@ impl Net {
                 return;
             }
         };
-        // For now, treat "fuse" as a heal.
-        n.heal();
-        println!("fuse {}: heal() applied", who);
+        match n.fuse_thread(parent) {
+            Ok(digest) => {
+                println!("fused_digest = {:?}", digest);
+                if let Some(Content::Retina(r)) = n.inbox.last().map(|m| &m.content) {
+                    println!(
+                        "sources = {}  fused_variance_drop = {}  psnr = {} dB",
+                        r.fused_from.len(),
+                        r.cert.fused_variance_drop,
+                        r.cert.psnr_equiv_db
+                    );
+                }
+            }
+            Err(e) => eprintln!("fuse {} failed: {}", who, e),
+        }
     }
 
     fn cmd_open_blob(&self, who: &str, idx: usize, path: &str) {
@@ -276,7 +450,7 @@ impl Net {
             }
         };
 
-        let msg = match n.inbox.get(idx) {
+        let msg = match n.inbox.at(idx) {
             Some(m) => m,
             None => {
                 eprintln!("no such message index {}", idx);
@@ -286,13 +460,16 @@ impl Net {
 
         match &msg.content {
             Content::Blob(body) => {
-                match store::get(&body.object_digest) {
-                    Ok(bytes) => {
-                        if let Err(e) = std::fs::write(path, &bytes) {
-                            eprintln!("write {} failed: {}", path, e);
-                        } else {
-                            println!("wrote {} bytes to {}", bytes.len(), path);
-                        }
+                let file = match std::fs::File::create(path) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        eprintln!("write {} failed: {}", path, e);
+                        return;
+                    }
+                };
+                match store::get_stream(n.store.as_ref(), &body.object_digest, io::BufWriter::new(file)) {
+                    Ok(n) => {
+                        println!("wrote {} bytes to {}", n, path);
                     }
                     Err(e) => {
                         eprintln!("CAS get failed: {}", e);
@@ -318,7 +495,11 @@ fn print_banner() {
     println!("  poll WHO");
     println!("  inbox WHO");
     println!("  rep WHO");
-    println!("  fuse WHO");
+    println!("  attest WHO");
+    println!("  quarantine WHO");
+    println!("  release WHO INDEX [restore]");
+    println!("  discard WHO INDEX");
+    println!("  fuse WHO root|last");
     println!("  open_blob WHO INDEX PATH");
     println!("  help");
     println!("  quit / exit");
@@ -438,13 +619,58 @@ fn main() {
                 }
             }
 
+            "attest" => {
+                if parts.len() != 2 {
+                    eprintln!("usage: attest WHO");
+                } else {
+                    let who = parts[1];
+                    net.cmd_attest(who);
+                }
+            }
+
             "fuse" => {
+                if parts.len() != 3 {
+                    eprintln!("usage: fuse WHO root|last");
+                } else {
+                    let who = parts[1];
+                    net.cmd_fuse(who, parts[2]);
+                }
+            }
+
+            "quarantine" => {
                 if parts.len() != 2 {
-                    eprintln!("usage: fuse WHO");
+                    eprintln!("usage: quarantine WHO");
                 } else {
                     let who = parts[1];
-                    net.cmd_fuse(who);
+                    net.cmd_quarantine(who);
+                }
+            }
+
+            "release" | "discard" => {
+                let usage = if cmd == "release" {
+                    "usage: release WHO INDEX [restore]"
+                } else {
+                    "usage: discard WHO INDEX"
+                };
+                let restore = parts.len() == 4 && parts[3] == "restore" && cmd == "release";
+                if parts.len() != 3 && !restore {
+                    eprintln!("{}", usage);
+                    continue;
                 }
+                let who = parts[1];
+                let idx: usize = match parts[2].parse() {
+                    Ok(i) => i,
+                    Err(e) => {
+                        eprintln!("bad INDEX {}: {}", parts[2], e);
+                        continue;
+                    }
+                };
+                let review = if cmd == "release" {
+                    Review::Release { restore }
+                } else {
+                    Review::Discard
+                };
+                net.cmd_review(who, idx, review);
             }
 
             "open_blob" => {
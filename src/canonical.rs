@@ -0,0 +1,763 @@
+//! Canonical encoding used for every digest.
+//!
+//! `serde_json::to_string` is not a stable preimage: its float printing,
+//! field order and escaping are implementation details. This module
+//! serializes any `Serialize` value into a fully specified byte string
+//! instead, so that digests agree across builds, serde versions and ports
+//! of the protocol to other languages.
+//!
+//! Encoding (all lengths/counts are "uint" encoded, see below):
+//!
+//! | value                | bytes                                         |
+//! |----------------------|-----------------------------------------------|
+//! | unit, `None`, unit struct | `00`                                     |
+//! | bool                 | `01` then `00` / `01`                         |
+//! | unsigned integer     | `02` then uint                                |
+//! | signed integer       | `03` then uint of the zigzag value            |
+//! | float (f32 or f64)   | `04` then 8 bytes big-endian IEEE-754 binary64|
+//! | string / char        | `05` then uint byte length, UTF-8 bytes       |
+//! | byte string          | `06` then uint length, raw bytes              |
+//! | `Some(v)`            | `07` then v                                   |
+//! | sequence / tuple     | `08` then uint count, items in order          |
+//! | map                  | `09` then uint count, (key, value) pairs sorted by encoded key |
+//! | struct               | `0a` then uint count, (name string, value) pairs sorted by name |
+//! | unit variant         | `0b` then variant name string                 |
+//! | newtype variant      | `0c` then name string, value                  |
+//! | tuple variant        | `0d` then name string, sequence               |
+//! | struct variant       | `0e` then name string, struct                 |
+//!
+//! Newtype structs are transparent (`Digest([u8; 32])` encodes as its array).
+//!
+//! A "uint" is one length byte `n` (0..=16) followed by the value as `n`
+//! big-endian bytes with no leading zero byte; zero is the single byte `00`.
+//!
+//! Floats are always widened to binary64. `-0.0` is encoded as `+0.0` and
+//! every NaN as the quiet NaN `7ff8000000000000`, so numerically equal
+//! values cannot produce different digests.
+//!
+//! Map keys and struct field names are sorted by their encoded bytes, so
+//! neither `HashMap` iteration order nor Rust field declaration order
+//! affects the output.
+//!
+//! `from_bytes` reads the encoding back for values that are stored under
+//! their digest, such as chunk manifests.
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "canonical encoding: {}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+const TAG_UNIT: u8 = 0x00;
+const TAG_BOOL: u8 = 0x01;
+const TAG_UINT: u8 = 0x02;
+const TAG_SINT: u8 = 0x03;
+const TAG_FLOAT: u8 = 0x04;
+const TAG_STR: u8 = 0x05;
+const TAG_BYTES: u8 = 0x06;
+const TAG_SOME: u8 = 0x07;
+const TAG_SEQ: u8 = 0x08;
+const TAG_MAP: u8 = 0x09;
+const TAG_STRUCT: u8 = 0x0a;
+const TAG_UNIT_VARIANT: u8 = 0x0b;
+const TAG_NEWTYPE_VARIANT: u8 = 0x0c;
+const TAG_TUPLE_VARIANT: u8 = 0x0d;
+const TAG_STRUCT_VARIANT: u8 = 0x0e;
+
+const CANONICAL_NAN: u64 = 0x7ff8_0000_0000_0000;
+
+/// Encode `value` into its canonical byte string.
+pub fn to_bytes<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    value.serialize(Encoder { out: &mut out })?;
+    Ok(out)
+}
+
+fn put_uint(out: &mut Vec<u8>, v: u128) {
+    let bytes = v.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count();
+    out.push((16 - skip) as u8);
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.push(TAG_STR);
+    put_uint(out, s.len() as u128);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_float(out: &mut Vec<u8>, v: f64) {
+    let bits = if v.is_nan() {
+        CANONICAL_NAN
+    } else if v == 0.0 {
+        0
+    } else {
+        v.to_bits()
+    };
+    out.push(TAG_FLOAT);
+    out.extend_from_slice(&bits.to_be_bytes());
+}
+
+fn zigzag(v: i128) -> u128 {
+    ((v << 1) ^ (v >> 127)) as u128
+}
+
+struct Encoder<'a> {
+    out: &'a mut Vec<u8>,
+}
+
+/// Collects items of a sequence / tuple, which are written in order.
+struct SeqEncoder<'a> {
+    out: &'a mut Vec<u8>,
+    items: Vec<Vec<u8>>,
+}
+
+/// Collects encoded (key, value) entries of a map or struct; they are
+/// sorted by encoded key before being written.
+struct MapEncoder<'a> {
+    out: &'a mut Vec<u8>,
+    tag: u8,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    pending_key: Option<Vec<u8>>,
+}
+
+impl SeqEncoder<'_> {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(to_bytes(value)?);
+        Ok(())
+    }
+
+    fn finish(self) {
+        self.out.push(TAG_SEQ);
+        put_uint(self.out, self.items.len() as u128);
+        for item in self.items {
+            self.out.extend_from_slice(&item);
+        }
+    }
+}
+
+impl MapEncoder<'_> {
+    fn field<T: ?Sized + Serialize>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        let mut key = Vec::new();
+        put_str(&mut key, name);
+        self.entries.push((key, to_bytes(value)?));
+        Ok(())
+    }
+
+    fn finish(mut self) {
+        self.entries.sort();
+        self.out.push(self.tag);
+        put_uint(self.out, self.entries.len() as u128);
+        for (k, v) in self.entries {
+            self.out.extend_from_slice(&k);
+            self.out.extend_from_slice(&v);
+        }
+    }
+}
+
+impl<'a> Encoder<'a> {
+    fn seq(self) -> SeqEncoder<'a> {
+        SeqEncoder { out: self.out, items: Vec::new() }
+    }
+
+    fn map(self, tag: u8) -> MapEncoder<'a> {
+        MapEncoder { out: self.out, tag, entries: Vec::new(), pending_key: None }
+    }
+}
+
+impl<'a> ser::Serializer for Encoder<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqEncoder<'a>;
+    type SerializeTuple = SeqEncoder<'a>;
+    type SerializeTupleStruct = SeqEncoder<'a>;
+    type SerializeTupleVariant = SeqEncoder<'a>;
+    type SerializeMap = MapEncoder<'a>;
+    type SerializeStruct = MapEncoder<'a>;
+    type SerializeStructVariant = MapEncoder<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.out.push(TAG_BOOL);
+        self.out.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.out.push(TAG_SINT);
+        put_uint(self.out, zigzag(v));
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.out.push(TAG_UINT);
+        put_uint(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        put_float(self.out, v as f64);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        put_float(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        put_str(self.out, v.encode_utf8(&mut [0u8; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        put_str(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.out.push(TAG_BYTES);
+        put_uint(self.out, v.len() as u128);
+        self.out.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        self.out.push(TAG_SOME);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.out.push(TAG_UNIT);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.out.push(TAG_UNIT_VARIANT);
+        put_str(self.out, variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.out.push(TAG_NEWTYPE_VARIANT);
+        put_str(self.out, variant);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqEncoder<'a>, Error> {
+        Ok(self.seq())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<SeqEncoder<'a>, Error> {
+        Ok(self.seq())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<SeqEncoder<'a>, Error> {
+        Ok(self.seq())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqEncoder<'a>, Error> {
+        self.out.push(TAG_TUPLE_VARIANT);
+        put_str(self.out, variant);
+        Ok(self.seq())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapEncoder<'a>, Error> {
+        Ok(self.map(TAG_MAP))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<MapEncoder<'a>, Error> {
+        Ok(self.map(TAG_STRUCT))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapEncoder<'a>, Error> {
+        self.out.push(TAG_STRUCT_VARIANT);
+        put_str(self.out, variant);
+        Ok(self.map(TAG_STRUCT))
+    }
+}
+
+impl ser::SerializeSeq for SeqEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for SeqEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for MapEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.pending_key = Some(to_bytes(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .pending_key
+            .take()
+            .ok_or_else(|| Error("map value without key".to_string()))?;
+        self.entries.push((key, to_bytes(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for MapEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for MapEncoder<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+/// Decode a value from its canonical byte string. Anything `to_bytes`
+/// would not have produced is rejected: trailing bytes, non-minimal
+/// integers, unnormalized floats, and map keys or struct fields that are
+/// out of order or repeated.
+pub fn from_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, Error> {
+    let mut decoder = Decoder { input: bytes };
+    let value = T::deserialize(&mut decoder)?;
+    if !decoder.input.is_empty() {
+        return Err(Error("trailing bytes".to_string()));
+    }
+    Ok(value)
+}
+
+fn unzigzag(v: u128) -> i128 {
+    ((v >> 1) as i128) ^ -((v & 1) as i128)
+}
+
+struct Decoder<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Decoder<'de> {
+    fn take(&mut self, n: usize) -> Result<&'de [u8], Error> {
+        if self.input.len() < n {
+            return Err(Error("unexpected end of input".to_string()));
+        }
+        let (head, rest) = self.input.split_at(n);
+        self.input = rest;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Result<u8, Error> {
+        self.input
+            .first()
+            .copied()
+            .ok_or_else(|| Error("unexpected end of input".to_string()))
+    }
+
+    fn uint(&mut self) -> Result<u128, Error> {
+        let n = self.byte()? as usize;
+        if n > 16 {
+            return Err(Error(format!("uint length {} out of range", n)));
+        }
+        let bytes = self.take(n)?;
+        if bytes.first() == Some(&0) {
+            return Err(Error("uint with leading zero".to_string()));
+        }
+        Ok(bytes.iter().fold(0u128, |v, b| (v << 8) | *b as u128))
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        let n = self.uint()?;
+        usize::try_from(n).map_err(|_| Error(format!("length {} out of range", n)))
+    }
+
+    fn str(&mut self) -> Result<&'de str, Error> {
+        if self.byte()? != TAG_STR {
+            return Err(Error("expected string".to_string()));
+        }
+        let len = self.len()?;
+        std::str::from_utf8(self.take(len)?).map_err(|e| Error(e.to_string()))
+    }
+}
+
+/// Yields `left` items (or key/value pairs) of a sequence, map or struct.
+struct Items<'a, 'de> {
+    de: &'a mut Decoder<'de>,
+    left: usize,
+    // encoded bytes of the previous map key; each must sort after it
+    last_key: Option<&'de [u8]>,
+}
+
+impl<'de> de::SeqAccess<'de> for Items<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.left == 0 {
+            return Ok(None);
+        }
+        self.left -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Items<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.left == 0 {
+            return Ok(None);
+        }
+        self.left -= 1;
+        let before = self.de.input;
+        let key = seed.deserialize(&mut *self.de)?;
+        let encoded = &before[..before.len() - self.de.input.len()];
+        if self.last_key.is_some_and(|last| encoded <= last) {
+            return Err(Error("keys out of order or repeated".to_string()));
+        }
+        self.last_key = Some(encoded);
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+/// An enum variant whose tag byte has been read; the name comes next.
+struct Variant<'a, 'de> {
+    de: &'a mut Decoder<'de>,
+    tag: u8,
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for Variant<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let name = self.de.str()?;
+        let value = seed.deserialize(name.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.expect(TAG_UNIT_VARIANT)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        self.expect(TAG_NEWTYPE_VARIANT)?;
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.expect(TAG_TUPLE_VARIANT)?;
+        de::Deserializer::deserialize_any(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.expect(TAG_STRUCT_VARIANT)?;
+        de::Deserializer::deserialize_any(self.de, visitor)
+    }
+}
+
+impl Variant<'_, '_> {
+    fn expect(&self, tag: u8) -> Result<(), Error> {
+        if self.tag != tag {
+            return Err(Error(format!("variant tag {:#04x}, expected {:#04x}", self.tag, tag)));
+        }
+        Ok(())
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.byte()? {
+            TAG_UNIT => visitor.visit_unit(),
+            TAG_BOOL => match self.byte()? {
+                0 => visitor.visit_bool(false),
+                1 => visitor.visit_bool(true),
+                b => Err(Error(format!("bad bool byte {:#04x}", b))),
+            },
+            TAG_UINT => {
+                let v = self.uint()?;
+                match u64::try_from(v) {
+                    Ok(v) => visitor.visit_u64(v),
+                    Err(_) => visitor.visit_u128(v),
+                }
+            }
+            TAG_SINT => {
+                let v = unzigzag(self.uint()?);
+                match i64::try_from(v) {
+                    Ok(v) => visitor.visit_i64(v),
+                    Err(_) => visitor.visit_i128(v),
+                }
+            }
+            TAG_FLOAT => {
+                let bits = self.take(8)?;
+                let bits = u64::from_be_bytes(bits.try_into().expect("8 bytes"));
+                let v = f64::from_bits(bits);
+                if (v == 0.0 && bits != 0) || (v.is_nan() && bits != CANONICAL_NAN) {
+                    return Err(Error("unnormalized float".to_string()));
+                }
+                visitor.visit_f64(v)
+            }
+            TAG_STR => {
+                let len = self.len()?;
+                let s = std::str::from_utf8(self.take(len)?).map_err(|e| Error(e.to_string()))?;
+                visitor.visit_borrowed_str(s)
+            }
+            TAG_BYTES => {
+                let len = self.len()?;
+                visitor.visit_borrowed_bytes(self.take(len)?)
+            }
+            TAG_SOME => visitor.visit_some(self),
+            TAG_SEQ => {
+                let left = self.len()?;
+                visitor.visit_seq(Items { de: self, left, last_key: None })
+            }
+            TAG_MAP | TAG_STRUCT => {
+                let left = self.len()?;
+                visitor.visit_map(Items { de: self, left, last_key: None })
+            }
+            tag @ (TAG_UNIT_VARIANT | TAG_NEWTYPE_VARIANT | TAG_TUPLE_VARIANT | TAG_STRUCT_VARIANT) => {
+                visitor.visit_enum(Variant { de: self, tag })
+            }
+            tag => Err(Error(format!("unknown tag {:#04x}", tag))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek()? {
+            TAG_UNIT => {
+                self.byte()?;
+                visitor.visit_none()
+            }
+            TAG_SOME => {
+                self.byte()?;
+                visitor.visit_some(self)
+            }
+            tag => Err(Error(format!("expected option, found tag {:#04x}", tag))),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
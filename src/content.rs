@@ -1,5 +1,5 @@
 use serde::{Serialize, Deserialize};
-use crate::types::{Digest, Timestamp};
+use crate::types::{Digest, PubKey, Timestamp, compute_digest};
 use crate::blob::BlobBody;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +8,29 @@ pub enum Content {
     Retina(RetinaBody),
     Status(StatusEvent),
     Blob(BlobBody),
+    Attestation(AttestationBody),
+}
+
+/// Which variant a `Content` is, for indexing without the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentKind {
+    Text,
+    Retina,
+    Status,
+    Blob,
+    Attestation,
+}
+
+impl Content {
+    pub fn kind(&self) -> ContentKind {
+        match self {
+            Content::Text(_) => ContentKind::Text,
+            Content::Retina(_) => ContentKind::Retina,
+            Content::Status(_) => ContentKind::Status,
+            Content::Blob(_) => ContentKind::Blob,
+            Content::Attestation(_) => ContentKind::Attestation,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,9 +46,12 @@ pub struct RetinaBody {
     pub lambda: f64,
     pub foveation: FoveationSpec,
     pub cert: CertBundle,
+    /// digests of the retina messages this body was fused from; empty
+    /// for a single capture
+    pub fused_from: Vec<Digest>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BasisSpec {
     pub nx: u32,
     pub ny: u32,
@@ -44,6 +70,13 @@ pub struct CertBundle {
     pub psnr_equiv_db: f64,
     pub fused_variance_drop: f64,
     pub foveation_alignment_score: f64,
+    /// capture nonce from the evidence; two captures of the same scene
+    /// still get distinct certificates
+    pub seed: u64,
+    /// digest of the samples the solve was fit to (for a fused body, of
+    /// the inputs' samples digests)
+    pub samples_digest: Digest,
+    /// hex of `certificate_hash` over the rest of the body
     pub deterministic_hash: String,
 }
 
@@ -55,6 +88,16 @@ pub enum StatusEvent {
     TypingStop,
 }
 
+/// Most subjects one attestation may score.
+pub const MAX_ATTESTED: usize = 1024;
+
+/// The sender's first-hand reputation scores of other peers, sorted by
+/// subject. The message signature makes the sender the attester.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationBody {
+    pub scores: Vec<(PubKey, f64)>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub sender: crate::types::PubKey,
@@ -64,3 +107,68 @@ pub struct Message {
     pub signature: crate::types::Signature,
     pub timestamp: Timestamp,
 }
+
+/// Canonical digest preimage: the header fields plus the content.
+/// Binding sender/parent/timestamp makes authorship and thread position
+/// tamper-evident; the domain tag keeps it from colliding with other
+/// digests computed over the same structures.
+#[derive(Serialize)]
+struct MessagePreimage<'a> {
+    domain: &'static str,
+    sender: &'a PubKey,
+    parent: &'a Digest,
+    timestamp: &'a Timestamp,
+    content: &'a Content,
+}
+
+pub fn message_digest(
+    sender: &PubKey,
+    parent: &Digest,
+    timestamp: &Timestamp,
+    content: &Content,
+) -> Digest {
+    compute_digest(&MessagePreimage {
+        domain: "collapse/message/v1",
+        sender,
+        parent,
+        timestamp,
+        content,
+    })
+}
+
+/// Preimage of a retina certificate hash: every field of the body except
+/// the hash itself and `fused_from`, which is provenance rather than part
+/// of the solve (the message signature already covers it).
+#[derive(Serialize)]
+struct CertPreimage<'a> {
+    domain: &'static str,
+    omega_id: &'a str,
+    basis_spec: &'a BasisSpec,
+    a_hat: &'a [f64],
+    lambda: f64,
+    foveation: &'a FoveationSpec,
+    psnr_equiv_db: f64,
+    fused_variance_drop: f64,
+    foveation_alignment_score: f64,
+    seed: u64,
+    samples_digest: &'a Digest,
+}
+
+/// Hex digest binding a retina certificate to the solve it describes.
+/// `cert.deterministic_hash` is ignored, so this can fill it in.
+pub fn certificate_hash(body: &RetinaBody) -> String {
+    let d = compute_digest(&CertPreimage {
+        domain: "collapse/retina-cert/v1",
+        omega_id: &body.omega_id,
+        basis_spec: &body.basis_spec,
+        a_hat: &body.a_hat,
+        lambda: body.lambda,
+        foveation: &body.foveation,
+        psnr_equiv_db: body.cert.psnr_equiv_db,
+        fused_variance_drop: body.cert.fused_variance_drop,
+        foveation_alignment_score: body.cert.foveation_alignment_score,
+        seed: body.cert.seed,
+        samples_digest: &body.cert.samples_digest,
+    });
+    d.0.iter().map(|b| format!("{:02x}", b)).collect()
+}
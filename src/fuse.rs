@@ -1,4 +1,8 @@
-use crate::content::RetinaBody;
+use std::fmt;
+
+use crate::content::{CertBundle, FoveationSpec, RetinaBody, certificate_hash};
+use crate::retina::PSNR_CAP_DB;
+use crate::store::digest_to_hex;
 use crate::types::{Digest, compute_digest};
 
 #[derive(Debug, Clone)]
@@ -7,34 +11,151 @@ pub struct FusedRetina {
     pub fused_digest: Digest,
 }
 
+/// Why a set of fixations could not be fused. `index` is the position of
+/// the offending input; input 0 is the reference the others must match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuseError {
+    Empty,
+    /// a source digest with no retina body behind it
+    UnknownSource { digest: Digest },
+    /// a source whose fixation `digest` an earlier source already covers,
+    /// directly or through its `fused_from`
+    Overlap { index: usize, digest: Digest },
+    OmegaMismatch { index: usize, expected: String, got: String },
+    BasisMismatch { index: usize, expected: String, got: String },
+    CoefficientCount { index: usize, expected: usize, got: usize },
+    NonFinite { index: usize },
+}
+
+impl fmt::Display for FuseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuseError::Empty => write!(f, "no fixations to fuse"),
+            FuseError::UnknownSource { digest } => {
+                write!(f, "no retina message {}", digest_to_hex(digest))
+            }
+            FuseError::Overlap { index, digest } => {
+                write!(f, "fixation {}: {} is already fused in", index, digest_to_hex(digest))
+            }
+            FuseError::OmegaMismatch { index, expected, got } => {
+                write!(f, "fixation {}: omega {} != {}", index, got, expected)
+            }
+            FuseError::BasisMismatch { index, expected, got } => {
+                write!(f, "fixation {}: basis {} != {}", index, got, expected)
+            }
+            FuseError::CoefficientCount { index, expected, got } => {
+                write!(f, "fixation {}: {} coefficients, expected {}", index, got, expected)
+            }
+            FuseError::NonFinite { index } => {
+                write!(f, "fixation {}: non-finite coefficients or certificate", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FuseError {}
+
+/// Error variance of one fixation's estimate, relative to the signal peak.
+/// A fused input already reports the PSNR of its fused estimate.
+fn fixation_variance(cert: &CertBundle) -> f64 {
+    10f64.powf(-cert.psnr_equiv_db.clamp(0.0, PSNR_CAP_DB) / 10.0)
+}
+
 /// fuse_fixations:
 /// Input: slice of RetinaBody packets from successive fixations / saccades
 /// Output: canonical fused RetinaBody + its digest
 ///
-/// TODO:
-/// - Stack all a_hat from inputs
-/// - Recompute cert:
-///     fused_variance_drop ~ 1 / sum_j M_j
-/// - Solve global fuse of coefficients
+/// All inputs must describe the same scene (`omega_id`) in the same basis.
+/// Coefficients are combined by inverse-variance weighting, with each
+/// fixation's variance taken from its certificate:
+///   var_j = 10^(-psnr_j / 10),            w_j = 1 / var_j
+///   a_hat = sum_j w_j a_j / sum_j w_j,    var = 1 / sum_j w_j
+/// The fused certificate reports the PSNR of `var`, the weighted mean
+/// alignment, and `fused_variance_drop = var / mean_j(var_j)`, which is at
+/// most 1/J (equal to it when all fixations are equally good).
 ///
-/// For now:
-/// - if multiple captures, we pick the first but adjust cert.fused_variance_drop
-///   to reflect number of fixations, so digest changes deterministically with J.
-pub fn fuse_fixations(retinas: &[RetinaBody]) -> Option<FusedRetina> {
-    let first = retinas.first()?.clone();
-    let j = retinas.len() as f64;
-
-    let mut fused = first.clone();
-    if j > 0.0 {
-        // rewrite fused_variance_drop to reflect 1 / total_fixations
-        let new_drop = 1.0 / j;
-        let mut new_cert = fused.cert.clone();
-        new_cert.fused_variance_drop = new_drop;
-        fused.cert = new_cert;
+/// `fused_from` is left empty; callers that know the source messages
+/// (see `NodeMessenger::fuse_digests`) fill it in.
+pub fn fuse_fixations(retinas: &[RetinaBody]) -> Result<FusedRetina, FuseError> {
+    let first = retinas.first().ok_or(FuseError::Empty)?;
+    let n = first.a_hat.len();
+
+    for (index, r) in retinas.iter().enumerate() {
+        if r.omega_id != first.omega_id {
+            return Err(FuseError::OmegaMismatch {
+                index,
+                expected: first.omega_id.clone(),
+                got: r.omega_id.clone(),
+            });
+        }
+        if r.basis_spec != first.basis_spec {
+            return Err(FuseError::BasisMismatch {
+                index,
+                expected: first.basis_spec.basis_fingerprint.clone(),
+                got: r.basis_spec.basis_fingerprint.clone(),
+            });
+        }
+        let expected = (r.basis_spec.nx * r.basis_spec.ny) as usize;
+        if r.a_hat.len() != expected {
+            return Err(FuseError::CoefficientCount { index, expected, got: r.a_hat.len() });
+        }
+        let cert = &r.cert;
+        let finite = r.a_hat.iter().all(|a| a.is_finite())
+            && cert.psnr_equiv_db.is_finite()
+            && cert.fused_variance_drop.is_finite()
+            && cert.foveation_alignment_score.is_finite();
+        if !finite {
+            return Err(FuseError::NonFinite { index });
+        }
+    }
+
+    let variances: Vec<f64> = retinas.iter().map(|r| fixation_variance(&r.cert)).collect();
+    let weights: Vec<f64> = variances.iter().map(|v| 1.0 / v).collect();
+    let total: f64 = weights.iter().sum();
+    let weighted_mean = |f: &dyn Fn(&RetinaBody) -> f64| -> f64 {
+        retinas.iter().zip(&weights).map(|(r, w)| w * f(r)).sum::<f64>() / total
+    };
+
+    let mut a_hat = vec![0.0; n];
+    for (r, w) in retinas.iter().zip(&weights) {
+        for (acc, a) in a_hat.iter_mut().zip(&r.a_hat) {
+            *acc += w * a;
+        }
+    }
+    for acc in a_hat.iter_mut() {
+        *acc /= total;
     }
 
+    let fused_var = 1.0 / total;
+    let mean_var = variances.iter().sum::<f64>() / variances.len() as f64;
+    let cert = CertBundle {
+        psnr_equiv_db: (-10.0 * fused_var.log10()).clamp(0.0, PSNR_CAP_DB),
+        fused_variance_drop: (fused_var / mean_var).min(1.0),
+        foveation_alignment_score: weighted_mean(&|r| r.cert.foveation_alignment_score),
+        seed: first.cert.seed,
+        samples_digest: compute_digest(
+            &retinas.iter().map(|r| &r.cert.samples_digest).collect::<Vec<_>>(),
+        ),
+        deterministic_hash: String::new(),
+    };
+
+    let mut fused = RetinaBody {
+        omega_id: first.omega_id.clone(),
+        basis_spec: first.basis_spec.clone(),
+        a_hat,
+        lambda: weighted_mean(&|r| r.lambda),
+        foveation: FoveationSpec {
+            sigma: weighted_mean(&|r| r.foveation.sigma),
+            center_x: weighted_mean(&|r| r.foveation.center_x),
+            center_y: weighted_mean(&|r| r.foveation.center_y),
+        },
+        cert,
+        fused_from: Vec::new(),
+    };
+    fused.cert.deterministic_hash = certificate_hash(&fused);
+
     let dig = compute_digest(&fused);
-    Some(FusedRetina {
+    Ok(FusedRetina {
         fused,
         fused_digest: dig,
     })
@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use crate::content::{Content, Message};
+use crate::store::{BlobStore, Manifest};
+use crate::types::Digest;
+
+/// Outcome of a sweep (or of a dry run, which deletes nothing).
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    pub dry_run: bool,
+    /// objects kept because something references them
+    pub live: usize,
+    /// unreferenced objects (deleted unless `dry_run`)
+    pub swept: Vec<Digest>,
+    pub reclaimable_bytes: u64,
+}
+
+/// Reference counts for every object reachable from `messages` and
+/// `pins`: each blob's manifest, plus each chunk the manifest lists.
+/// A chunk shared by several blobs is counted once per blob.
+pub fn live_set<'a>(
+    store: &dyn BlobStore,
+    messages: impl IntoIterator<Item = &'a Message>,
+    pins: &HashSet<Digest>,
+) -> HashMap<Digest, usize> {
+    let mut refs: HashMap<Digest, usize> = HashMap::new();
+
+    let roots = messages
+        .into_iter()
+        .filter_map(|m| match &m.content {
+            Content::Blob(b) => Some(b.object_digest.clone()),
+            _ => None,
+        })
+        .chain(pins.iter().cloned());
+
+    for root in roots {
+        *refs.entry(root.clone()).or_default() += 1;
+        let manifest = match store.get(&root).ok().and_then(|b| Manifest::parse(&b)) {
+            Some(m) => m,
+            None => continue,
+        };
+        for c in manifest.chunks {
+            *refs.entry(c.digest).or_default() += 1;
+        }
+    }
+    refs
+}
+
+/// Sweep every stored object that `live` does not reference.
+pub fn collect(
+    store: &mut dyn BlobStore,
+    live: &HashMap<Digest, usize>,
+    dry_run: bool,
+) -> io::Result<GcReport> {
+    let mut report = GcReport { dry_run, ..GcReport::default() };
+    for digest in store.list()? {
+        if live.contains_key(&digest) {
+            report.live += 1;
+            continue;
+        }
+        report.reclaimable_bytes += store.size(&digest).unwrap_or(0);
+        if !dry_run {
+            store.delete(&digest)?;
+        }
+        report.swept.push(digest);
+    }
+    Ok(report)
+}
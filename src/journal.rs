@@ -0,0 +1,300 @@
+//! On-disk node state: an append-only log plus periodic snapshots.
+//!
+//! A node directory holds
+//! - `key`: the 32-byte Ed25519 secret seed of the node identity,
+//! - `log`: records appended since the last snapshot,
+//! - `snapshot.json`: the full state as of record `seq`,
+//! - `cas/`: the node's blob store.
+//!
+//! Each log frame is `len: u32 BE | check: [u8; 4] | payload`, where the
+//! payload is the JSON of one `Entry` and `check` is the first four bytes
+//! of its SHA-256. A crash can leave a torn frame at the end of the log;
+//! `Journal::open` keeps every intact frame before it and truncates the
+//! rest, so later appends start from a clean boundary.
+//!
+//! A snapshot is written to a temp file, synced and renamed over the old
+//! one, and only then is the log emptied. Every entry carries a sequence
+//! number, so entries already covered by a snapshot are skipped if a
+//! crash lands between the rename and the truncation.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as ShaDigest, Sha256};
+
+use crate::content::Message;
+use crate::keys::{Keypair, SecretKey};
+use crate::reputation::{PeerScore, ReputationBook, ReputationEvent};
+use crate::types::PubKey;
+
+/// Records appended between snapshots before a new snapshot is taken.
+pub const SNAPSHOT_EVERY: u64 = 256;
+
+const LOG_FILE: &str = "log";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const KEY_FILE: &str = "key";
+
+/// One state change.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Record {
+    /// a message accepted into the inbox
+    Accepted(Message),
+    /// every reputation score that changed since the previous record
+    Scores(Vec<(PubKey, PeerScore)>),
+    /// one entry of the reputation audit log
+    Event(ReputationEvent),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    record: Record,
+}
+
+/// Node state rebuilt from the snapshot and the log.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeState {
+    /// last sequence number this state includes
+    pub seq: u64,
+    /// accepted messages in arrival order
+    pub messages: Vec<Message>,
+    /// scores as last changed; fading since then is applied on load
+    pub scores: Vec<(PubKey, PeerScore)>,
+    #[serde(default)]
+    pub events: Vec<ReputationEvent>,
+}
+
+impl NodeState {
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Accepted(msg) => self.messages.push(msg),
+            Record::Event(e) => self.events.push(e),
+            Record::Scores(changed) => {
+                for (who, score) in changed {
+                    match self.scores.iter_mut().find(|(p, _)| *p == who) {
+                        Some(entry) => entry.1 = score,
+                        None => self.scores.push((who, score)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct Journal {
+    dir: PathBuf,
+    log: File,
+    seq: u64,
+    since_snapshot: u64,
+    // scores as of the last Scores record, to log only what changed
+    saved_scores: HashMap<PubKey, PeerScore>,
+    // reputation events already logged (see ReputationBook::recorded)
+    saved_events: u64,
+}
+
+impl Journal {
+    /// Open (or create) the journal in `dir` and rebuild the state it
+    /// holds.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<(Journal, NodeState)> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut state = match fs::read(dir.join(SNAPSHOT_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => NodeState::default(),
+            Err(e) => return Err(e),
+        };
+
+        let log_path = dir.join(LOG_FILE);
+        let bytes = match fs::read(&log_path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let (entries, good_len) = read_frames(&bytes);
+        let mut since_snapshot = 0;
+        for entry in entries {
+            if entry.seq <= state.seq {
+                continue;
+            }
+            state.seq = entry.seq;
+            state.apply(entry.record);
+            since_snapshot += 1;
+        }
+
+        let log = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        if good_len < bytes.len() {
+            eprintln!(
+                "journal: dropping {} bytes of torn log tail in {}",
+                bytes.len() - good_len,
+                dir.display()
+            );
+            log.set_len(good_len as u64)?;
+            log.sync_data()?;
+        }
+
+        let journal = Journal {
+            dir,
+            log,
+            seq: state.seq,
+            since_snapshot,
+            saved_scores: state.scores.iter().cloned().collect(),
+            saved_events: state.events.len() as u64,
+        };
+        Ok((journal, state))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append one record and sync it to disk. If that fails the log is cut
+    /// back to where it was, so a torn frame cannot hide the records
+    /// appended after it from the next open.
+    pub fn append(&mut self, record: Record) -> io::Result<()> {
+        let entry = Entry { seq: self.seq + 1, record };
+        let payload = serde_json::to_vec(&entry)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record too large"))?;
+
+        let mut frame = Vec::with_capacity(8 + payload.len());
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&checksum(&payload));
+        frame.extend_from_slice(&payload);
+        let start = self.log.metadata()?.len();
+        if let Err(e) = self.log.write_all(&frame).and_then(|()| self.log.sync_data()) {
+            if let Err(cut) = self.log.set_len(start) {
+                eprintln!("journal: cannot cut torn record from {}: {}", self.dir.display(), cut);
+            }
+            return Err(e);
+        }
+
+        self.seq += 1;
+        self.since_snapshot += 1;
+        Ok(())
+    }
+
+    /// Log the reputation events recorded and the scores changed since
+    /// the last call, if any.
+    pub fn append_reputation(&mut self, rep: &ReputationBook) -> io::Result<()> {
+        for event in rep.events_since(self.saved_events) {
+            self.append(Record::Event(event))?;
+        }
+        self.saved_events = rep.recorded();
+
+        let scores = rep.saved_scores();
+        let changed: Vec<(PubKey, PeerScore)> = scores
+            .iter()
+            .filter(|(who, s)| self.saved_scores.get(*who) != Some(*s))
+            .map(|(who, s)| (who.clone(), *s))
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        self.append(Record::Scores(changed))?;
+        self.saved_scores = scores;
+        Ok(())
+    }
+
+    pub fn should_snapshot(&self) -> bool {
+        self.since_snapshot >= SNAPSHOT_EVERY
+    }
+
+    /// Write the full state and start an empty log.
+    pub fn snapshot<'a>(
+        &mut self,
+        messages: impl IntoIterator<Item = &'a Message>,
+        rep: &ReputationBook,
+    ) -> io::Result<()> {
+        let mut scores: Vec<(PubKey, PeerScore)> = rep.saved_scores().into_iter().collect();
+        scores.sort_by_key(|(who, _)| who.0);
+        let state = NodeState {
+            seq: self.seq,
+            messages: messages.into_iter().cloned().collect(),
+            scores,
+            events: rep.events().cloned().collect(),
+        };
+        let bytes = serde_json::to_vec(&state)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tmp = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        {
+            let mut f = File::create(&tmp)?;
+            f.write_all(&bytes)?;
+            f.sync_all()?;
+        }
+        fs::rename(&tmp, self.dir.join(SNAPSHOT_FILE))?;
+
+        self.log.set_len(0)?;
+        self.log.sync_data()?;
+        self.since_snapshot = 0;
+        self.saved_scores = rep.saved_scores();
+        self.saved_events = rep.recorded();
+        Ok(())
+    }
+}
+
+/// Decode intact frames from the start of `bytes`; also returns where the
+/// intact prefix ends.
+fn read_frames(bytes: &[u8]) -> (Vec<Entry>, usize) {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while bytes.len() - pos >= 8 {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        let start = pos + 8;
+        let payload = match bytes.get(start..start + len) {
+            Some(p) => p,
+            None => break,
+        };
+        if checksum(payload) != bytes[pos + 4..pos + 8] {
+            break;
+        }
+        match serde_json::from_slice(payload) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => break,
+        }
+        pos = start + len;
+    }
+    (entries, pos)
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let h = Sha256::digest(payload);
+    [h[0], h[1], h[2], h[3]]
+}
+
+/// The identity stored in `dir`, or a fresh one saved there.
+pub fn load_or_create_key(dir: impl AsRef<Path>) -> io::Result<Keypair> {
+    let path = dir.as_ref().join(KEY_FILE);
+    match File::open(&path) {
+        Ok(mut f) => {
+            let mut seed = [0u8; 32];
+            f.read_exact(&mut seed)?;
+            Ok(Keypair::from_secret(SecretKey(seed)))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::create_dir_all(dir.as_ref())?;
+            let key = Keypair::generate();
+            let tmp = dir.as_ref().join(format!("{}.tmp", KEY_FILE));
+            {
+                let mut opts = OpenOptions::new();
+                opts.write(true).create(true).truncate(true);
+                // the seed is the identity: keep it private to the owner
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+                let mut f = opts.open(&tmp)?;
+                f.write_all(&key.secret().0)?;
+                f.sync_all()?;
+            }
+            fs::rename(&tmp, &path)?;
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+
+use crate::types::{Digest, PubKey, Signature};
+
+/// Ed25519 secret seed. Never serialized, never printed.
+#[derive(Clone)]
+pub struct SecretKey(pub [u8; 32]);
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(<redacted>)")
+    }
+}
+
+/// A node identity: Ed25519 secret seed + derived public key.
+#[derive(Clone, Debug)]
+pub struct Keypair {
+    pub public: PubKey,
+    secret: SecretKey,
+}
+
+impl Keypair {
+    /// Fresh random identity from the OS-seeded CSPRNG.
+    pub fn generate() -> Self {
+        Self::from_secret(SecretKey(rand::random()))
+    }
+
+    /// Deterministic identity (tests, demos, restoring a saved seed).
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self::from_secret(SecretKey(seed))
+    }
+
+    pub fn from_secret(secret: SecretKey) -> Self {
+        let sk = SigningKey::from_bytes(&secret.0);
+        let public = PubKey(sk.verifying_key().to_bytes());
+        Self { public, secret }
+    }
+
+    pub fn secret(&self) -> &SecretKey {
+        &self.secret
+    }
+
+    pub fn sign(&self, d: &Digest) -> Signature {
+        let sk = SigningKey::from_bytes(&self.secret.0);
+        Signature(sk.sign(&d.0).to_bytes().to_vec())
+    }
+}
+
+/// Check an Ed25519 signature over a digest. Malformed keys or
+/// signatures simply fail verification.
+pub fn verify_signature(pk: &PubKey, d: &Digest, sig: &Signature) -> bool {
+    let vk = match VerifyingKey::from_bytes(&pk.0) {
+        Ok(vk) => vk,
+        Err(_) => return false,
+    };
+    let sig = match ed25519_dalek::Signature::from_slice(&sig.0) {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    vk.verify(&d.0, &sig).is_ok()
+}
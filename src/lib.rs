@@ -1,12 +1,19 @@
 pub mod types;
+pub mod canonical;
+pub mod keys;
 pub mod content;
 pub mod blob;
 pub mod store;
+pub mod gc;
 pub mod phi;
+pub mod retina;
 pub mod reputation;
+pub mod message_store;
 pub mod verify;
+pub mod journal;
 pub mod node;
 pub mod fuse;
 pub mod wire;
 pub mod transport;
 pub mod transport_mem;
+pub mod transport_tcp;
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::Index;
+
+use crate::content::{ContentKind, Message};
+use crate::types::{Digest, PubKey};
+
+/// Accepted messages in arrival order, indexed by digest, parent,
+/// sender and content kind. A parent is always accepted before its
+/// children, so arrival order is also a causal order.
+#[derive(Debug, Clone, Default)]
+pub struct MessageStore {
+    messages: Vec<Message>,
+    by_digest: HashMap<Digest, usize>,
+    by_parent: HashMap<Digest, Vec<usize>>,
+    by_sender: HashMap<PubKey, Vec<usize>>,
+    by_kind: HashMap<ContentKind, Vec<usize>>,
+}
+
+impl MessageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `msg`; returns false (and stores nothing) if its digest is
+    /// already present.
+    pub fn insert(&mut self, msg: Message) -> bool {
+        if self.by_digest.contains_key(&msg.digest) {
+            return false;
+        }
+        let i = self.messages.len();
+        self.by_digest.insert(msg.digest.clone(), i);
+        self.by_parent.entry(msg.parent.clone()).or_default().push(i);
+        self.by_sender.entry(msg.sender.clone()).or_default().push(i);
+        self.by_kind.entry(msg.content.kind()).or_default().push(i);
+        self.messages.push(msg);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// All messages in arrival order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Message> {
+        self.messages.iter()
+    }
+
+    pub fn last(&self) -> Option<&Message> {
+        self.messages.last()
+    }
+
+    /// The `index`-th message in arrival order.
+    pub fn at(&self, index: usize) -> Option<&Message> {
+        self.messages.get(index)
+    }
+
+    pub fn get(&self, digest: &Digest) -> Option<&Message> {
+        self.by_digest.get(digest).map(|&i| &self.messages[i])
+    }
+
+    pub fn contains(&self, digest: &Digest) -> bool {
+        self.by_digest.contains_key(digest)
+    }
+
+    fn lookup(&self, indices: Option<&Vec<usize>>) -> Vec<&Message> {
+        indices
+            .map(|v| v.iter().map(|&i| &self.messages[i]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Direct replies to `digest`, in arrival order. Replies to the zero
+    /// digest are the thread roots.
+    pub fn children(&self, digest: &Digest) -> Vec<&Message> {
+        self.lookup(self.by_parent.get(digest))
+    }
+
+    pub fn by_sender(&self, sender: &PubKey) -> Vec<&Message> {
+        self.lookup(self.by_sender.get(sender))
+    }
+
+    pub fn by_kind(&self, kind: ContentKind) -> Vec<&Message> {
+        self.lookup(self.by_kind.get(&kind))
+    }
+
+    /// Parent, grandparent, ... of `digest`, nearest first, as far back as
+    /// we hold them. Empty if `digest` itself is unknown.
+    pub fn ancestors(&self, digest: &Digest) -> Vec<&Message> {
+        let mut out = Vec::new();
+        let mut cursor = self.get(digest).and_then(|m| self.get(&m.parent));
+        while let Some(m) = cursor {
+            out.push(m);
+            cursor = self.get(&m.parent);
+        }
+        out
+    }
+
+    /// `root` and all its descendants, in causal (arrival) order.
+    pub fn thread(&self, root: &Digest) -> Vec<&Message> {
+        let mut found = Vec::new();
+        let mut queue: VecDeque<usize> = self.by_digest.get(root).copied().into_iter().collect();
+        while let Some(i) = queue.pop_front() {
+            found.push(i);
+            if let Some(kids) = self.by_parent.get(&self.messages[i].digest) {
+                queue.extend(kids);
+            }
+        }
+        found.sort_unstable();
+        found.into_iter().map(|i| &self.messages[i]).collect()
+    }
+}
+
+impl Index<usize> for MessageStore {
+    type Output = Message;
+
+    fn index(&self, index: usize) -> &Message {
+        &self.messages[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a MessageStore {
+    type Item = &'a Message;
+    type IntoIter = std::slice::Iter<'a, Message>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.iter()
+    }
+}
@@ -1,46 +1,256 @@
-use std::rc::Rc;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::path::Path;
 
-use crate::content::{Message, Content, RetinaBody, StatusEvent};
-use crate::types::{PubKey, Digest, now_timestamp, Timestamp};
+use crate::content::{Message, Content, RetinaBody, StatusEvent, MAX_ATTESTED};
+use crate::keys::Keypair;
+use crate::message_store::MessageStore;
+use crate::types::{PubKey, Digest, now_timestamp, zero_digest, Timestamp};
+use crate::store::{self, BlobStore, FsStore, FsckReport};
+use crate::gc::{self, GcReport};
+use crate::journal::{self, Journal, Record};
+use crate::fuse::{fuse_fixations, FuseError};
 use crate::reputation::ReputationBook;
-use crate::verify::{verify_digest, verify_thread};
+use crate::verify::{verify_content, verify_digest, verify_thread, VerifyError};
 use crate::phi::{phi_collapse, assemble_message, Evidence};
 use crate::transport::Transport;
-use crate::transport_mem::MemoryTransport;
+use crate::wire::Frame;
+
+/// How long an orphan may wait for its parent before it counts as
+/// a "missing parent" violation.
+pub const DEFAULT_PENDING_TIMEOUT_MS: u128 = 30_000;
+
+/// How many orphans may wait for a parent at once, in total and per
+/// sender. Without a bound, a peer minting throwaway keys could fill
+/// memory with orphans until they expire.
+pub const DEFAULT_PENDING_CAP: usize = 4096;
+pub const DEFAULT_PENDING_PER_SENDER: usize = 64;
+
+/// Minimum time between two history requests for orphans' parents.
+/// Parents missing in between are asked for together in the next one.
+pub const HISTORY_REQUEST_INTERVAL_MS: u128 = 1_000;
+
+/// How many quarantined messages a node holds before dropping the
+/// oldest.
+pub const DEFAULT_QUARANTINE_CAP: usize = 1024;
+
+/// How many ancestors of a missing digest we ask peers for in one
+/// history request.
+pub const HISTORY_DEPTH: u32 = 64;
+
+/// A verified message whose parent has not been accepted yet.
+#[derive(Debug, Clone)]
+pub struct PendingMessage {
+    pub msg: Message,
+    pub received_at: Timestamp,
+}
+
+/// A verified message held back because its sender was below the
+/// admission threshold when it arrived.
+#[derive(Debug, Clone)]
+pub struct QuarantinedMessage {
+    pub msg: Message,
+    pub received_at: Timestamp,
+    /// the sender's score on arrival
+    pub score: f64,
+}
+
+/// What `review_quarantine` does with one quarantined message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Review {
+    Keep,
+    /// accept it into the inbox (see `NodeMessenger::release`)
+    Release { restore: bool },
+    /// drop it and punish the sender
+    Discard,
+}
+
+/// A message that made it into the inbox, with the buffered orphans
+/// that were waiting on it: those accepted after it (in causal order)
+/// and those refused when they were finally checked.
+#[derive(Debug, Clone)]
+pub struct Accepted {
+    pub digest: Digest,
+    pub released: Vec<Digest>,
+    pub refused: Vec<Rejection>,
+}
+
+/// A message that did not make it into the inbox, and why. An orphan
+/// that was buffered to wait for its parent is reported as
+/// `MissingParent` with `punished == false`; if the parent never comes
+/// it is reported again by `expire_pending`, this time punished.
+/// Likewise a message from a sender below the admission threshold is
+/// quarantined and reported as `BelowTrustThreshold`, unpunished; see
+/// `release` and `discard`.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub digest: Digest,
+    pub sender: PubKey,
+    pub error: VerifyError,
+    pub punished: bool,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} from {}: {}", self.digest, self.sender, self.error)
+    }
+}
+
+/// Outcome of receiving one message.
+pub type Receipt = Result<Accepted, Rejection>;
 
 /// Collapse Messenger node with:
-/// - inbox of accepted canonical messages
+/// - inbox of accepted canonical messages, indexed by digest
+/// - pending buffer of orphans waiting for their parent
+/// - quarantine of messages from senders below the admission threshold
 /// - reputation book
 /// - retina_store cache
+/// - its own blob store, and the set of objects requested from peers
+///   but not yet stored
 /// - awareness of peers by PubKey
-/// - access to a shared transport bus
+/// - a transport to reach them (any `Transport` implementation)
 pub struct NodeMessenger {
     pub id: PubKey,
-    pub inbox: Vec<Message>,
+    key: Keypair,
+    pub inbox: MessageStore,
     pub rep: ReputationBook,
     pub retina_store: HashMap<Digest, RetinaBody>,
 
+    // orphans keyed by the parent digest they are waiting for
+    pub pending: HashMap<Digest, Vec<PendingMessage>>,
+    pub pending_timeout_ms: u128,
+    pub pending_cap: usize,
+    pub pending_per_sender: usize,
+
+    // orphans' parents not yet asked for, and when we last asked
+    unrequested: HashSet<Digest>,
+    last_history_request: Option<Timestamp>,
+
+    // messages from senders below the admission threshold, oldest
+    // first, waiting to be released or discarded
+    pub quarantine: VecDeque<QuarantinedMessage>,
+    pub quarantine_cap: usize,
+
+    // content-addressed storage for blob manifests and chunks
+    pub store: Box<dyn BlobStore>,
+
+    // blob objects we asked peers for and are still waiting on
+    pub wanted_blobs: HashSet<Digest>,
+
+    // blob objects kept by gc even when no message references them
+    pub pins: HashSet<Digest>,
+
     // who we talk to
     pub peers: Vec<PubKey>,
 
-    // shared transport (for now MemoryTransport via Rc<RefCell<...>>)
-    pub bus: Rc<RefCell<MemoryTransport>>,
+    // how frames reach peers (MemoryTransport, TCP, ...)
+    pub bus: Box<dyn Transport>,
+
+    // on-disk log and snapshots, for nodes created with open()
+    journal: Option<Journal>,
 }
 
 impl NodeMessenger {
-    pub fn new(id: PubKey, bus: Rc<RefCell<MemoryTransport>>) -> Self {
-        // register ourselves on the bus
-        bus.borrow_mut().register_peer(id.clone());
+    /// Create a node on any transport. A bus shared between several
+    /// in-process nodes is passed as an `Rc<RefCell<...>>` handle.
+    pub fn new(key: Keypair, bus: impl Transport + 'static) -> Self {
+        Self::with_transport(key, Box::new(bus))
+    }
+
+    /// Node on a boxed transport, storing blobs under `.cas/<its key>`.
+    pub fn with_transport(key: Keypair, bus: Box<dyn Transport>) -> Self {
+        let store = FsStore::for_node(&key.public);
+        Self::with_store(key, bus, Box::new(store))
+    }
+
+    /// Node with an explicit blob store (e.g. `FsStore` at its own root,
+    /// or `MemStore` in tests).
+    pub fn with_store(
+        key: Keypair,
+        mut bus: Box<dyn Transport>,
+        store: Box<dyn BlobStore>,
+    ) -> Self {
+        let id = key.public.clone();
+
+        // let the transport know how to route to us
+        bus.register(&id);
 
         Self {
             id,
-            inbox: Vec::new(),
+            key,
+            inbox: MessageStore::new(),
             rep: ReputationBook::new(),
             retina_store: HashMap::new(),
+            pending: HashMap::new(),
+            pending_timeout_ms: DEFAULT_PENDING_TIMEOUT_MS,
+            pending_cap: DEFAULT_PENDING_CAP,
+            pending_per_sender: DEFAULT_PENDING_PER_SENDER,
+            unrequested: HashSet::new(),
+            last_history_request: None,
+            quarantine: VecDeque::new(),
+            quarantine_cap: DEFAULT_QUARANTINE_CAP,
+            store,
+            wanted_blobs: HashSet::new(),
+            pins: HashSet::new(),
             peers: Vec::new(),
             bus,
+            journal: None,
+        }
+    }
+
+    /// Resume the node saved in `dir`, or start a new one there. The
+    /// identity, accepted messages (and with them the retina cache) and
+    /// reputation scores survive restarts; blobs live in `dir/cas`.
+    /// Pending orphans, quarantined messages and outstanding blob
+    /// requests do not: peers resend those through history and blob
+    /// requests.
+    pub fn open(dir: impl AsRef<Path>, bus: Box<dyn Transport>) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        let key = journal::load_or_create_key(dir)?;
+        let (journal, state) = Journal::open(dir)?;
+        let mut node = Self::with_store(key, bus, Box::new(FsStore::new(dir.join("cas"))));
+
+        for msg in state.messages {
+            if let Content::Retina(ref r) = msg.content {
+                node.retina_store.insert(msg.digest.clone(), r.clone());
+            }
+            if let Content::Attestation(ref a) = msg.content {
+                if msg.sender != node.id {
+                    node.rep.attest(&msg.sender, &a.scores, msg.timestamp);
+                }
+            }
+            node.rep.note_accepted(&msg.sender);
+            node.inbox.insert(msg);
+        }
+        for (who, score) in state.scores {
+            node.rep.restore(&who, score);
+        }
+        node.rep.restore_events(state.events);
+        node.journal = Some(journal);
+        Ok(node)
+    }
+
+    /// Write a snapshot now (no-op for nodes without a directory).
+    pub fn save(&mut self) -> io::Result<()> {
+        match self.journal.as_mut() {
+            Some(j) => j.snapshot(self.inbox.iter(), &self.rep),
+            None => Ok(()),
+        }
+    }
+
+    /// Persist reputation changes and take a snapshot when one is due.
+    fn checkpoint(&mut self) {
+        let journal = match self.journal.as_mut() {
+            Some(j) => j,
+            None => return,
+        };
+        let mut result = journal.append_reputation(&self.rep);
+        if result.is_ok() && journal.should_snapshot() {
+            result = journal.snapshot(self.inbox.iter(), &self.rep);
+        }
+        if let Err(e) = result {
+            eprintln!("journal write failed: {}", e);
         }
     }
 
@@ -49,35 +259,158 @@ impl NodeMessenger {
     }
 
     /// User action: produce evidence, collapse (Φ), sign, broadcast.
-    /// This is "send a new message into the conversation."
-    pub fn send(&mut self, parent: Digest, ev: Evidence) {
-        let now = now_timestamp();
-        let content = phi_collapse(ev);
-        let msg = assemble_message(&self.id, parent, content, now);
+    /// This is "send a new message into the conversation." Nothing is
+    /// sent if blob evidence cannot be read or stored.
+    pub fn send(&mut self, parent: Digest, ev: Evidence) -> io::Result<Digest> {
+        let content = phi_collapse(ev, self.store.as_mut())?;
+        Ok(self.publish(parent, content))
+    }
+
+    /// Sign already-canonical content, apply it locally and broadcast it.
+    fn publish(&mut self, parent: Digest, content: Content) -> Digest {
+        let msg = assemble_message(&self.key, parent, content, now_timestamp());
+        let digest = msg.digest.clone();
 
         // We always apply our own receive rules locally
-        self.receive_internal(&msg);
+        let _ = self.receive_internal(&msg);
 
         // Broadcast to peers (transport-level, not direct calls)
-        {
-            let mut bus = self.bus.borrow_mut();
-            // broadcast to all registered peers other than self
-            bus.broadcast(&self.id, &msg);
+        // broadcast to all registered peers other than self
+        self.bus.broadcast(&self.id, &Frame::Message(msg));
+        self.checkpoint();
+        digest
+    }
+
+    /// Fuse the retina captures that reply to `parent` and publish the
+    /// result as a new reply to `parent`. Earlier fusions in the thread
+    /// are not fed back in. Returns the digest of the fused message.
+    pub fn fuse_thread(&mut self, parent: Digest) -> Result<Digest, FuseError> {
+        let sources: Vec<Digest> = self
+            .inbox
+            .children(&parent)
+            .into_iter()
+            .filter(|m| match self.retina_store.get(&m.digest) {
+                Some(r) => r.fused_from.is_empty(),
+                None => false,
+            })
+            .map(|m| m.digest.clone())
+            .collect();
+        self.fuse_digests(parent, &sources)
+    }
+
+    /// Fuse the retina messages `sources` (in that order) from
+    /// `retina_store` and publish the fused body as a reply to `parent`,
+    /// recording `sources` in its `fused_from`. Repeated digests count
+    /// once; sources that share an underlying fixation are refused, since
+    /// fusing a capture with itself overstates the variance drop.
+    pub fn fuse_digests(&mut self, parent: Digest, sources: &[Digest]) -> Result<Digest, FuseError> {
+        let mut seen = HashSet::new();
+        let sources: Vec<Digest> = sources.iter().filter(|d| seen.insert(*d)).cloned().collect();
+
+        let mut covered = HashSet::new();
+        for (index, source) in sources.iter().enumerate() {
+            for digest in self.fixations_of(source) {
+                if !covered.insert(digest.clone()) {
+                    return Err(FuseError::Overlap { index, digest });
+                }
+            }
         }
+
+        let bodies = sources
+            .iter()
+            .map(|d| {
+                self.retina_store
+                    .get(d)
+                    .cloned()
+                    .ok_or_else(|| FuseError::UnknownSource { digest: d.clone() })
+            })
+            .collect::<Result<Vec<RetinaBody>, FuseError>>()?;
+
+        let mut fused = fuse_fixations(&bodies)?.fused;
+        fused.fused_from = sources;
+        Ok(self.publish(parent, Content::Retina(fused)))
     }
 
-    /// Poll the transport for inbound messages, run them through
-    /// verify_digest / verify_thread / reputation gate / reward/punish.
-    pub fn poll(&mut self) {
-        // drain messages destined for self.id
-        let inbound: Vec<Message> = {
-            let mut bus = self.bus.borrow_mut();
-            bus.drain_inbound(&self.id)
-        };
+    /// The single captures behind retina message `digest`: itself, or for
+    /// a fusion the captures behind each of its `fused_from`.
+    fn fixations_of(&self, digest: &Digest) -> Vec<Digest> {
+        let mut out = Vec::new();
+        let mut stack = vec![digest.clone()];
+        while let Some(d) = stack.pop() {
+            match self.retina_store.get(&d) {
+                Some(r) if !r.fused_from.is_empty() => stack.extend(r.fused_from.iter().cloned()),
+                _ => out.push(d),
+            }
+        }
+        out
+    }
 
-        for msg in inbound {
-            self.receive_internal(&msg);
+    /// Publish our first-hand score of every peer we have one for, so
+    /// others can blend it into theirs. Only local scores are attested:
+    /// repeating what others told us would let claims echo around.
+    pub fn publish_attestation(&mut self) -> Digest {
+        let mut scores: Vec<(PubKey, f64)> = self
+            .rep
+            .saved_scores()
+            .into_keys()
+            .filter(|who| *who != self.id)
+            .map(|who| {
+                let score = self.rep.local(&who);
+                (who, score)
+            })
+            .collect();
+        scores.sort_by_key(|(who, _)| who.0);
+        scores.truncate(MAX_ATTESTED);
+        let content = phi_collapse(Evidence::Attestation { scores }, self.store.as_mut())
+            .expect("attestations do not touch the store");
+        self.publish(zero_digest(), content)
+    }
+
+    /// Poll the transport for inbound frames:
+    /// - messages run through verify_digest / verify_thread /
+    ///   reputation gate / reward/punish,
+    /// - history requests are answered from our inbox,
+    /// - history responses are replayed like ordinary messages,
+    /// - blob requests are served from the CAS, blob responses stored.
+    ///
+    /// Then expire orphans that waited too long for their parent.
+    ///
+    /// Returns one receipt per message received (directly or as
+    /// history), followed by one per expired orphan.
+    pub fn poll(&mut self) -> Vec<Receipt> {
+        // drain frames destined for self.id
+        let inbound: Vec<Frame> = self.bus.drain_inbound(&self.id);
+        let mut receipts = Vec::new();
+
+        for frame in inbound {
+            if !frame.request_is_signed(now_timestamp()) {
+                eprintln!("⚠️ {} ignores an unsigned or stale request", self.id);
+                continue;
+            }
+            match frame {
+                Frame::Message(msg) => {
+                    receipts.push(self.receive_internal(&msg));
+                }
+                Frame::HistoryRequest { from, want, depth, .. } => {
+                    self.answer_history(&from, &want, depth);
+                }
+                Frame::HistoryResponse { messages, .. } => {
+                    receipts.extend(self.replay_history(&messages));
+                }
+                Frame::BlobRequest { from, digest, .. } => {
+                    self.answer_blob(&from, digest);
+                }
+                Frame::BlobResponse { from, digest, bytes } => {
+                    self.store_blob(&from, digest, &bytes);
+                }
+            }
         }
+
+        let now = now_timestamp();
+        let expired = self.expire_pending(now);
+        receipts.extend(expired.into_iter().map(Err));
+        self.flush_history_requests(now);
+        receipts
     }
 
     /// Send canonical "delivered" or "read" receipts for a given digest.
@@ -101,82 +434,480 @@ impl NodeMessenger {
 
     fn broadcast_status(&mut self, parent_digest: Digest, evt: StatusEvent, now: Timestamp) {
         let ev = Evidence::StatusIntent(evt);
-        let content = phi_collapse(ev);
-        let msg = assemble_message(&self.id, parent_digest, content, now);
+        let content = phi_collapse(ev, self.store.as_mut()).expect("status events do not touch the store");
+        let msg = assemble_message(&self.key, parent_digest, content, now);
 
         // apply locally
-        self.receive_internal(&msg);
+        let _ = self.receive_internal(&msg);
 
         // send to peers
-        {
-            let mut bus = self.bus.borrow_mut();
-            bus.broadcast(&self.id, &msg);
+        self.bus.broadcast(&self.id, &Frame::Message(msg));
+        self.checkpoint();
+    }
+
+    /// Core intake: admit one message, then release any buffered
+    /// orphans that were waiting for it (and their descendants).
+    fn receive_internal(&mut self, msg: &Message) -> Receipt {
+        self.admit(msg)?;
+        Ok(self.release_waiting(&msg.digest))
+    }
+
+    /// Admit the orphans that were waiting for the just accepted
+    /// `digest`, and theirs in turn.
+    fn release_waiting(&mut self, digest: &Digest) -> Accepted {
+        let mut accepted = Accepted {
+            digest: digest.clone(),
+            released: Vec::new(),
+            refused: Vec::new(),
+        };
+        let mut ready = vec![digest.clone()];
+        while let Some(parent) = ready.pop() {
+            let children = match self.pending.remove(&parent) {
+                Some(c) => c,
+                None => continue,
+            };
+            for p in children {
+                match self.admit(&p.msg) {
+                    Ok(()) => {
+                        accepted.released.push(p.msg.digest.clone());
+                        ready.push(p.msg.digest.clone());
+                    }
+                    Err(r) => accepted.refused.push(r),
+                }
+            }
         }
+        accepted
     }
 
-    /// Core intake:
+    /// Single-message checks:
     /// 1. verify digest/signature
-    /// 2. verify causality
-    /// 3. verify reputation gate
-    /// 4. accept+reward OR reject+punish
-    fn receive_internal(&mut self, msg: &Message) -> bool {
-        if !verify_digest(msg) {
-            self.reject_and_punish(msg, "bad digest/signature");
-            return false;
+    /// 2. ignore replays of messages already accepted
+    /// 3. verify content (retina certificates)
+    /// 4. verify causality (buffer the orphan if the parent is unknown)
+    /// 5. verify reputation gate (quarantine the message if the sender
+    ///    is below it)
+    /// 6. accept+reward OR reject+punish
+    ///
+    /// A bad signature means we cannot tell who really sent the message,
+    /// so it is dropped without touching the claimed sender's reputation
+    /// (otherwise anyone could burn a peer by forging in its name).
+    /// A replay is authentic but old news: neither rewarded nor punished,
+    /// since peers legitimately relay and re-answer history.
+    /// A sender below the threshold is not punished again for sending:
+    /// that would keep a once-punished peer down forever. Its messages
+    /// wait in quarantine for review instead.
+    fn admit(&mut self, msg: &Message) -> Result<(), Rejection> {
+        if let Err(e) = verify_digest(msg) {
+            return Err(self.reject(msg, e));
         }
 
-        if !verify_thread(msg, &self.inbox) {
-            self.reject_and_punish(msg, "missing parent");
-            return false;
+        if self.inbox.contains(&msg.digest) || self.is_quarantined(&msg.digest) {
+            return Err(self.reject(msg, VerifyError::Duplicate));
         }
 
-        let sender_rep = self.rep.get(&msg.sender);
-        if sender_rep < self.rep.admit_threshold() {
-            self.reject_and_punish(msg, "sender below trust threshold");
-            return false;
+        if let Err(e) = verify_content(msg) {
+            return Err(self.reject_and_punish(msg, e));
+        }
+
+        if let Err(e) = verify_thread(msg, &self.inbox) {
+            let e = match self.buffer_orphan(msg) {
+                Ok(()) => e,
+                Err(not_buffered) => not_buffered,
+            };
+            return Err(self.reject(msg, e));
+        }
+
+        let score = self.rep.get(&msg.sender);
+        let threshold = self.rep.admit_threshold();
+        if score < threshold {
+            self.quarantine_message(msg, score);
+            return Err(self.reject(msg, VerifyError::BelowTrustThreshold { score, threshold }));
         }
 
         self.accept_and_reward(msg);
-        true
+        Ok(())
+    }
+
+    /// Hold a verified message until its parent shows up. Reordering on
+    /// the network is not misbehaviour, so nobody is punished yet.
+    /// The first orphan for a given parent queues a history request
+    /// (see `flush_history_requests`).
+    /// Fails with `Duplicate` if the message was already waiting, or
+    /// `TooManyOrphans` if the buffer or the sender's share of it is
+    /// full.
+    fn buffer_orphan(&mut self, msg: &Message) -> Result<(), VerifyError> {
+        let waiting = self.pending.get(&msg.parent);
+        if waiting.is_some_and(|w| w.iter().any(|p| p.msg.digest == msg.digest)) {
+            return Err(VerifyError::Duplicate);
+        }
+        let from_sender = self
+            .pending
+            .values()
+            .flatten()
+            .filter(|p| p.msg.sender == msg.sender)
+            .count();
+        if self.pending_len() >= self.pending_cap || from_sender >= self.pending_per_sender {
+            return Err(VerifyError::TooManyOrphans);
+        }
+
+        let now = now_timestamp();
+        if waiting.is_none() {
+            self.unrequested.insert(msg.parent.clone());
+        }
+        self.pending.entry(msg.parent.clone()).or_default().push(PendingMessage {
+            msg: msg.clone(),
+            received_at: now,
+        });
+        self.flush_history_requests(now);
+        Ok(())
+    }
+
+    /// Ask peers for the parents queued by `buffer_orphan`, at most once
+    /// per `HISTORY_REQUEST_INTERVAL_MS`, so a flood of orphans does not
+    /// turn into a flood of requests. Also called from `poll`.
+    fn flush_history_requests(&mut self, now: Timestamp) {
+        // parents that arrived (or whose orphans expired) meanwhile, and
+        // parents we hold in quarantine, need no asking for
+        let pending = &self.pending;
+        let quarantine = &self.quarantine;
+        self.unrequested
+            .retain(|d| pending.contains_key(d) && !quarantine.iter().any(|q| q.msg.digest == *d));
+        if self.unrequested.is_empty() {
+            return;
+        }
+        if let Some(last) = self.last_history_request {
+            if now.0.saturating_sub(last.0) < HISTORY_REQUEST_INTERVAL_MS {
+                return;
+            }
+        }
+        let want: Vec<Digest> = self.unrequested.drain().collect();
+        self.last_history_request = Some(now);
+        self.request_history(want);
+    }
+
+    fn quarantine_message(&mut self, msg: &Message, score: f64) {
+        if self.quarantine.len() >= self.quarantine_cap {
+            if let Some(old) = self.quarantine.pop_front() {
+                eprintln!("quarantine full: dropping {:?}", old.msg.digest);
+            }
+        }
+        self.quarantine.push_back(QuarantinedMessage {
+            msg: msg.clone(),
+            received_at: now_timestamp(),
+            score,
+        });
+    }
+
+    pub fn is_quarantined(&self, digest: &Digest) -> bool {
+        self.quarantine.iter().any(|q| q.msg.digest == *digest)
+    }
+
+    fn take_quarantined(&mut self, digest: &Digest) -> Option<QuarantinedMessage> {
+        let idx = self.quarantine.iter().position(|q| q.msg.digest == *digest)?;
+        self.quarantine.remove(idx)
+    }
+
+    /// Accept a quarantined message into the inbox after all, with the
+    /// orphans that were waiting for it. With `restore` the sender is
+    /// also rewarded as for any accepted message, so a peer whose
+    /// messages keep being released climbs back over the threshold.
+    /// Returns None if `digest` is not quarantined.
+    pub fn release(&mut self, digest: &Digest, restore: bool) -> Option<Receipt> {
+        let q = self.take_quarantined(digest)?;
+        self.accept(&q.msg);
+        if restore {
+            self.rep.reward(&q.msg.sender, Some(&q.msg.digest));
+        }
+        let accepted = self.release_waiting(digest);
+        self.checkpoint();
+        Some(Ok(accepted))
+    }
+
+    /// Drop a quarantined message and punish its sender for it.
+    /// Returns None if `digest` is not quarantined.
+    pub fn discard(&mut self, digest: &Digest) -> Option<Rejection> {
+        let q = self.take_quarantined(digest)?;
+        let threshold = self.rep.admit_threshold();
+        let error = VerifyError::BelowTrustThreshold { score: q.score, threshold };
+        let rejection = self.reject_and_punish(&q.msg, error);
+        self.checkpoint();
+        Some(rejection)
+    }
+
+    /// Let `review` decide on every quarantined message, oldest first,
+    /// e.g. to release what senders sent before they recovered. Returns
+    /// a receipt per message released (`Ok`) or discarded (`Err`).
+    pub fn review_quarantine(
+        &mut self,
+        mut review: impl FnMut(&QuarantinedMessage, &ReputationBook) -> Review,
+    ) -> Vec<Receipt> {
+        let decisions: Vec<(Digest, Review)> = self
+            .quarantine
+            .iter()
+            .map(|q| (q.msg.digest.clone(), review(q, &self.rep)))
+            .collect();
+        let mut receipts = Vec::new();
+        for (digest, decision) in decisions {
+            let receipt = match decision {
+                Review::Keep => None,
+                Review::Release { restore } => self.release(&digest, restore),
+                Review::Discard => self.discard(&digest).map(Err),
+            };
+            receipts.extend(receipt);
+        }
+        receipts
+    }
+
+    /// Drop orphans that have waited longer than `pending_timeout_ms`
+    /// and punish their senders for the missing parent. Replies to a
+    /// quarantined message are kept: their parent did arrive, and they
+    /// are checked again when it is released.
+    pub fn expire_pending(&mut self, now: Timestamp) -> Vec<Rejection> {
+        let timeout = self.pending_timeout_ms;
+        let held: HashSet<&Digest> = self.quarantine.iter().map(|q| &q.msg.digest).collect();
+        let mut expired = Vec::new();
+        self.pending.retain(|parent, waiting| {
+            if held.contains(parent) {
+                return true;
+            }
+            waiting.retain(|p| {
+                let keep = now.0.saturating_sub(p.received_at.0) <= timeout;
+                if !keep {
+                    expired.push(p.msg.clone());
+                }
+                keep
+            });
+            !waiting.is_empty()
+        });
+
+        let rejections = expired
+            .iter()
+            .map(|msg| {
+                let parent = msg.parent.clone();
+                self.reject_and_punish(msg, VerifyError::MissingParent { parent })
+            })
+            .collect();
+
+        // poll() ends here too, so this persists everything it changed
+        self.checkpoint();
+        rejections
+    }
+
+    /// Number of orphans currently waiting for a parent.
+    pub fn pending_len(&self) -> usize {
+        self.pending.values().map(|v| v.len()).sum()
     }
 
     fn accept_and_reward(&mut self, msg: &Message) {
+        self.accept(msg);
+        self.rep.reward(&msg.sender, Some(&msg.digest));
+    }
+
+    fn accept(&mut self, msg: &Message) {
         // store message
-        self.inbox.push(msg.clone());
+        self.inbox.insert(msg.clone());
+        self.rep.note_accepted(&msg.sender);
+        if let Some(j) = self.journal.as_mut() {
+            if let Err(e) = j.append(Record::Accepted(msg.clone())) {
+                eprintln!("journal write failed: {}", e);
+            }
+        }
 
         // cache retinal witness for resurrection
         if let Content::Retina(ref r) = msg.content {
             self.retina_store.insert(msg.digest.clone(), r.clone());
         }
 
-        // reward sender
-        self.rep.reward(&msg.sender);
+        // only the blob's digest travels in the message; fetch the
+        // manifest and chunks from whoever sent it if we lack them
+        if let Content::Blob(ref b) = msg.content {
+            if msg.sender != self.id {
+                self.want_blob(&msg.sender, b.object_digest.clone());
+            }
+        }
+
+        // take in what the sender says about other peers
+        if let Content::Attestation(ref a) = msg.content {
+            if msg.sender != self.id {
+                self.rep.attest(&msg.sender, &a.scores, msg.timestamp);
+            }
+        }
     }
 
-    fn reject_and_punish(&mut self, msg: &Message, reason: &str) {
-        eprintln!(
-            "⚠️ {} rejects {:?}: {}",
-            self.id.0,
-            msg.digest,
-            reason
-        );
-        self.rep.punish(&msg.sender);
+    fn reject(&self, msg: &Message, error: VerifyError) -> Rejection {
+        Rejection {
+            digest: msg.digest.clone(),
+            sender: msg.sender.clone(),
+            error,
+            punished: false,
+        }
     }
 
-    /// Deterministic healing:
-    /// Instead of asking Rc peers directly, we now just
-    /// re-run causal acceptance as new messages arrive via poll().
-    /// With a real network, "heal" becomes:
-    /// - request thread history,
-    /// - replay them through receive_internal in order.
-    ///
-    /// We'll keep a stub here in case we pipeline it later.
+    fn reject_and_punish(&mut self, msg: &Message, error: VerifyError) -> Rejection {
+        let offense = match error.offense() {
+            Some(o) => o,
+            None => return self.reject(msg, error),
+        };
+        self.rep.punish(&msg.sender, offense, Some(&msg.digest));
+        Rejection {
+            punished: true,
+            ..self.reject(msg, error)
+        }
+    }
+
+    /// Healing: ask peers again for every parent our buffered orphans
+    /// are still waiting on. Answers arrive through poll() and are
+    /// replayed through receive_internal in causal order.
     pub fn heal(&mut self) {
-        // No-op for now: poll() + replay is our heal mechanism in transport mode.
-        // Future: ask bus (or peer) for missing history by digest.
+        let missing: Vec<Digest> = self.pending.keys().cloned().collect();
+        if !missing.is_empty() {
+            self.request_history(missing);
+        }
+    }
+
+    fn request_history(&mut self, want: Vec<Digest>) {
+        let req = Frame::history_request(&self.key, want, HISTORY_DEPTH, now_timestamp());
+        self.bus.broadcast(&self.id, &req);
+    }
+
+    /// Reply with each wanted message we hold plus up to `depth` of its
+    /// ancestors, oldest first. Silence if we hold none of them.
+    fn answer_history(&mut self, to: &PubKey, want: &[Digest], depth: u32) {
+        let depth = depth.min(HISTORY_DEPTH);
+        let mut messages: Vec<Message> = Vec::new();
+
+        for d in want {
+            let mut chain = Vec::new();
+            let mut cursor = self.inbox.get(d);
+            while let Some(m) = cursor {
+                if chain.len() as u32 > depth {
+                    break;
+                }
+                chain.push(m.clone());
+                cursor = self.inbox.get(&m.parent);
+            }
+            for m in chain.into_iter().rev() {
+                if !messages.iter().any(|x| x.digest == m.digest) {
+                    messages.push(m);
+                }
+            }
+        }
+
+        if messages.is_empty() {
+            return;
+        }
+
+        let resp = Frame::HistoryResponse {
+            from: self.id.clone(),
+            messages,
+        };
+        self.bus.send_to(to, &resp);
+    }
+
+    /// Replay answered history. Messages we already hold are skipped so
+    /// several peers answering the same request is harmless.
+    fn replay_history(&mut self, messages: &[Message]) -> Vec<Receipt> {
+        let mut receipts = Vec::new();
+        for msg in messages {
+            if self.inbox.contains(&msg.digest) {
+                continue;
+            }
+            receipts.push(self.receive_internal(msg));
+        }
+        receipts
     }
 
-    pub fn decay_reputation(&mut self) {
-        self.rep.decay();
+    /// Request whatever part of a blob is missing locally: the manifest
+    /// first, or the chunks it lists once the manifest is stored.
+    fn want_blob(&mut self, from: &PubKey, digest: Digest) {
+        if !self.store.has(&digest) {
+            self.request_blob(from, digest);
+            return;
+        }
+        for chunk in store::missing_chunks(self.store.as_ref(), &digest) {
+            self.request_blob(from, chunk);
+        }
+    }
+
+    /// Ask one peer for a blob object.
+    pub fn request_blob(&mut self, from: &PubKey, digest: Digest) {
+        self.wanted_blobs.insert(digest.clone());
+        let req = Frame::blob_request(&self.key, digest, now_timestamp());
+        self.bus.send_to(from, &req);
+    }
+
+    /// Ask every peer for a blob object (e.g. when the original sender
+    /// is gone).
+    pub fn fetch_blob(&mut self, digest: Digest) {
+        self.wanted_blobs.insert(digest.clone());
+        let req = Frame::blob_request(&self.key, digest, now_timestamp());
+        self.bus.broadcast(&self.id, &req);
+    }
+
+    /// Scan our store, quarantine corrupt objects and ask peers for
+    /// fresh copies; they arrive (verified) through poll().
+    pub fn repair_store(&mut self) -> io::Result<FsckReport> {
+        let report = store::fsck(self.store.as_mut(), true)?;
+        for digest in &report.corrupt {
+            self.fetch_blob(digest.clone());
+        }
+        Ok(report)
+    }
+
+    /// Keep a blob (and its chunks) through gc regardless of references.
+    pub fn pin_blob(&mut self, digest: Digest) {
+        self.pins.insert(digest);
+    }
+
+    pub fn unpin_blob(&mut self, digest: &Digest) {
+        self.pins.remove(digest);
+    }
+
+    /// Sweep store objects not referenced by an accepted or still-pending
+    /// message, nor pinned. With `dry_run` only report what would go.
+    pub fn gc(&mut self, dry_run: bool) -> io::Result<GcReport> {
+        let pending = self.pending.values().flatten().map(|p| &p.msg);
+        let live = gc::live_set(
+            self.store.as_ref(),
+            self.inbox.iter().chain(pending),
+            &self.pins,
+        );
+        gc::collect(self.store.as_mut(), &live, dry_run)
+    }
+
+    fn answer_blob(&mut self, to: &PubKey, digest: Digest) {
+        let bytes = match self.store.get(&digest) {
+            Ok(b) => b,
+            Err(_) => return,
+        };
+        let resp = Frame::BlobResponse { from: self.id.clone(), digest, bytes };
+        self.bus.send_to(to, &resp);
+    }
+
+    /// Store blob bytes we asked for, after checking they hash to the
+    /// requested digest. Unsolicited objects are ignored, and so are bytes
+    /// that do not match: `from` is whatever the frame claims, so punishing
+    /// it would let anyone frame an honest peer.
+    fn store_blob(&mut self, from: &PubKey, digest: Digest, bytes: &[u8]) {
+        if !self.wanted_blobs.contains(&digest) {
+            return;
+        }
+        if store::object_digest(bytes) != digest {
+            eprintln!(
+                "⚠️ {} rejects blob {:?} from {}: hash mismatch",
+                self.id,
+                digest,
+                from
+            );
+            return;
+        }
+        match self.store.put(bytes) {
+            Ok(_) => {
+                self.wanted_blobs.remove(&digest);
+                // a manifest pulls in its chunks from the same peer
+                self.want_blob(from, digest);
+            }
+            Err(e) => eprintln!("CAS write failed: {}", e),
+        }
     }
 }
@@ -1,3 +1,7 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+
 use crate::content::{
     Content,
     TextBody,
@@ -6,11 +10,16 @@ use crate::content::{
     CertBundle,
     FoveationSpec,
     StatusEvent,
+    AttestationBody,
     Message,
+    message_digest,
+    certificate_hash,
 };
 use crate::blob::BlobBody;
-use crate::types::{PubKey, Digest, Timestamp, compute_digest, sign_digest};
-use crate::store;
+use crate::keys::Keypair;
+use crate::types::{Digest, PubKey, Timestamp, compute_digest, sign_digest};
+use crate::store::{self, BlobStore, Manifest};
+use crate::retina;
 
 /// New evidence kinds that Φ can collapse into canonical Content.
 #[derive(Clone, Debug)]
@@ -18,7 +27,8 @@ pub enum Evidence {
     /// Free-form text to canonicalize (trim + collapse whitespace).
     DraftText { raw: String },
 
-    /// Synthetic/demo retinal capture (placeholder solve right now).
+    /// Retinal capture: (x, y, value) samples on the unit square, fit by
+    /// a regularized least-squares solve (see `retina`).
     RawRetinaCapture {
         samples: Vec<(f32,f32,f32)>,
         lambda: f32,
@@ -32,49 +42,72 @@ pub enum Evidence {
     /// Canonical status events (delivered/read/typing) to be wrapped as Content::Status.
     StatusIntent(StatusEvent),
 
+    /// Reputation scores of other peers to vouch for (or warn about).
+    Attestation { scores: Vec<(PubKey, f64)> },
+
     /// Arbitrary binary payload (pictures, gifs, video, docs...) with MIME.
     Blob { bytes: Vec<u8>, mime: String },
+
+    /// Same as Blob, but streamed from a file chunk by chunk so large
+    /// media never has to fit in memory.
+    BlobFile { path: PathBuf, mime: String },
 }
 
-/// Core collapse implementation.
-pub fn collapse_evidence(e: Evidence) -> Content {
-    match e {
+/// Core collapse implementation. Blob evidence is written into `store`;
+/// only its digest ends up in the Content. Fails only for blob evidence,
+/// when the file cannot be read or the store cannot be written.
+pub fn collapse_evidence(e: Evidence, store: &mut dyn BlobStore) -> io::Result<Content> {
+    let content = match e {
         Evidence::DraftText { raw } => {
             let canonical = raw.split_whitespace().collect::<Vec<_>>().join(" ");
             Content::Text(TextBody { canonical_text: canonical })
         }
 
-        Evidence::RawRetinaCapture { lambda, foveation_cfg, basis_cfg, .. } => {
+        Evidence::RawRetinaCapture { samples, lambda, foveation_cfg, basis_cfg, cert_seed } => {
             let (sigma, cx, cy) = foveation_cfg;
             let (nx, ny) = basis_cfg;
 
-            // Placeholder coefficients (7-dim fused vector) for the demo pipeline.
-            let a_hat: Vec<f64> = vec![0.1, 0.2, 0.05, 0.0, -0.03, 0.07, 0.12];
+            let samples: Vec<(f64, f64, f64)> = samples
+                .iter()
+                .map(|(x, y, v)| (*x as f64, *y as f64, *v as f64))
+                .collect();
+            let fit = retina::solve(
+                &samples,
+                lambda as f64,
+                sigma as f64,
+                (cx as f64, cy as f64),
+                nx,
+                ny,
+            );
 
-            // Minimal certificate bundle with deterministic placeholders.
+            // A single fixation: no fusion yet, so no variance drop.
             let cert = CertBundle {
-                psnr_equiv_db: 80.0_f64,
-                fused_variance_drop: 0.0_f64,
-                deterministic_hash: "demo-cert".to_string(),
-                foveation_alignment_score: 1.0_f64,
+                psnr_equiv_db: fit.psnr_equiv_db,
+                fused_variance_drop: 1.0_f64,
+                foveation_alignment_score: fit.alignment,
+                seed: cert_seed,
+                samples_digest: compute_digest(&samples),
+                deterministic_hash: String::new(),
             };
 
-            let retina = RetinaBody {
+            let mut retina = RetinaBody {
                 lambda: lambda as f64,
                 omega_id: "omega/0".to_string(),
                 basis_spec: BasisSpec {
                     nx,
                     ny,
-                    basis_fingerprint: "basis/demo".to_string(),
+                    basis_fingerprint: retina::basis_fingerprint(nx, ny),
                 },
                 foveation: FoveationSpec {
                     sigma: sigma as f64,
                     center_x: cx as f64,
                     center_y: cy as f64,
                 },
-                a_hat,
+                a_hat: fit.a_hat,
                 cert,
+                fused_from: Vec::new(),
             };
+            retina.cert.deterministic_hash = certificate_hash(&retina);
 
             Content::Retina(retina)
         }
@@ -83,31 +116,50 @@ pub fn collapse_evidence(e: Evidence) -> Content {
             Content::Status(evt)
         }
 
+        Evidence::Attestation { mut scores } => {
+            scores.sort_by_key(|(who, _)| who.0);
+            scores.dedup_by(|a, b| a.0 == b.0);
+            Content::Attestation(AttestationBody { scores })
+        }
+
         Evidence::Blob { bytes, mime } => {
             let len = bytes.len();
-            let object_digest = store::put(&bytes).expect("CAS write failed");
+            let object_digest = store::put_blob(store, &bytes)?;
             let body = BlobBody { mime, len, object_digest };
             Content::Blob(body)
         }
-    }
+
+        Evidence::BlobFile { path, mime } => {
+            let file = File::open(&path)?;
+            let object_digest = store::put_stream(store, BufReader::new(file))?;
+            // the length is what was actually chunked, not what the file
+            // claimed to be when it was opened
+            let manifest = Manifest::parse(&store.get(&object_digest)?)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a manifest"))?;
+            let body = BlobBody { mime, len: manifest.total_len as usize, object_digest };
+            Content::Blob(body)
+        }
+    };
+    Ok(content)
 }
 
 /// Public collapse entry used by NodeMessenger.
-pub fn phi_collapse(e: Evidence) -> Content {
-    collapse_evidence(e)
+pub fn phi_collapse(e: Evidence, store: &mut dyn BlobStore) -> io::Result<Content> {
+    collapse_evidence(e, store)
 }
 
-/// Assemble a signed, digested message.
+/// Assemble a signed, digested message. The sender is the keypair's public key;
+/// the digest covers sender, parent, timestamp and content.
 pub fn assemble_message(
-    sender: &PubKey,
+    key: &Keypair,
     parent: Digest,
     content: Content,
     timestamp: Timestamp,
 ) -> Message {
-    let digest = compute_digest(&content);
-    let signature = sign_digest(sender, &digest);
+    let digest = message_digest(&key.public, &parent, &timestamp, &content);
+    let signature = sign_digest(key, &digest);
     Message {
-        sender: sender.clone(),
+        sender: key.public.clone(),
         parent,
         content,
         digest,
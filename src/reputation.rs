@@ -1,54 +1,650 @@
-use std::collections::HashMap;
-use crate::types::PubKey;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 
-pub struct ReputationBook {
-    scores: HashMap<PubKey, f64>,
-    reward_step: f64,
-    punish_step: f64,
-    floor: f64,
-    ceiling: f64,
-    neutral: f64,
-    admit_threshold: f64,
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Digest, PubKey, Timestamp, now_timestamp};
+
+/// Default `ReputationConfig::half_life_ms`: one day.
+pub const DEFAULT_HALF_LIFE_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// How many events a ReputationBook keeps; older ones are dropped.
+pub const EVENT_LOG_CAP: usize = 4096;
+
+/// How far ahead of our clock an attestation may be dated. Later ones
+/// are refused: they would never age out and would shut out the
+/// attester's real attestations until our clock caught up.
+pub const MAX_CLOCK_SKEW_MS: u128 = 5 * 60 * 1000;
+
+/// What a peer is being punished for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Offense {
+    /// signed content that fails its own checks (e.g. a forged certificate)
+    MalformedContent,
+    /// an orphan whose parent never arrived
+    MissingParent,
+    /// a message quarantined for a low score, then discarded on review
+    BelowThreshold,
 }
 
-impl ReputationBook {
-    pub fn new() -> Self {
+/// Why a score moved.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EventReason {
+    /// a message from the peer was accepted
+    Accepted,
+    Punished(Offense),
+}
+
+/// One score change, for auditing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationEvent {
+    pub who: PubKey,
+    /// local score after minus local score before, both as of `at` (0
+    /// if the score was already at a bound)
+    pub delta: f64,
+    pub reason: EventReason,
+    /// the message (or blob) that caused it, if any
+    pub digest: Option<Digest>,
+    pub at: Timestamp,
+}
+
+/// A peer's score as last changed, at time `at`. Policies that fade
+/// scores over time apply the fading when the score is read, so this is
+/// what gets saved and restored.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PeerScore {
+    pub score: f64,
+    pub at: Timestamp,
+}
+
+/// JSON export of a book: current scores (fading applied) and the
+/// retained event log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationExport {
+    pub scores: Vec<(PubKey, f64)>,
+    pub events: Vec<ReputationEvent>,
+}
+
+/// Parameters of the default `StepPolicy`. Every field has a default, so
+/// a JSON config only needs the values it changes:
+///
+/// ```json
+/// { "punish_step": 0.3, "weights": { "MissingParent": 0.5 } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReputationConfig {
+    pub reward_step: f64,
+    pub punish_step: f64,
+    pub floor: f64,
+    pub ceiling: f64,
+    pub neutral: f64,
+    pub admit_threshold: f64,
+    /// time for a score to fade halfway back to neutral; 0 disables
+    /// fading
+    pub half_life_ms: u64,
+    /// punishment multiplier per offense; missing entries weigh 1.0
+    pub weights: HashMap<Offense, f64>,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
         Self {
-            scores: HashMap::new(),
             reward_step: 0.1,
             punish_step: 0.2,
             floor: 0.0,
             ceiling: 1.0,
             neutral: 0.5,
             admit_threshold: 0.30,
+            half_life_ms: DEFAULT_HALF_LIFE_MS,
+            weights: HashMap::new(),
+        }
+    }
+}
+
+/// Why a `ReputationConfig` was refused.
+#[derive(Debug)]
+pub enum ConfigError {
+    Parse(serde_json::Error),
+    NotFinite { field: &'static str },
+    /// steps are magnitudes; the sign comes from reward vs punish
+    NegativeStep { field: &'static str },
+    /// bounds must satisfy floor <= neutral <= ceiling
+    Bounds { floor: f64, neutral: f64, ceiling: f64 },
+    BadWeight { offense: Offense, weight: f64 },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Parse(e) => write!(f, "reputation config: {}", e),
+            ConfigError::NotFinite { field } => write!(f, "{} is not a finite number", field),
+            ConfigError::NegativeStep { field } => write!(f, "{} is negative", field),
+            ConfigError::Bounds { floor, neutral, ceiling } => write!(
+                f,
+                "need floor <= neutral <= ceiling, got {} / {} / {}",
+                floor, neutral, ceiling
+            ),
+            ConfigError::BadWeight { offense, weight } => {
+                write!(f, "weight {} for {:?} is not a non-negative number", weight, offense)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ReputationConfig {
+    /// Parse and validate a JSON config.
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let config: Self = serde_json::from_str(json).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the values `StepPolicy` relies on: finite numbers,
+    /// non-negative steps and weights, floor <= neutral <= ceiling.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let numbers = [
+            ("reward_step", self.reward_step),
+            ("punish_step", self.punish_step),
+            ("floor", self.floor),
+            ("ceiling", self.ceiling),
+            ("neutral", self.neutral),
+            ("admit_threshold", self.admit_threshold),
+        ];
+        if let Some((field, _)) = numbers.iter().find(|(_, v)| !v.is_finite()) {
+            return Err(ConfigError::NotFinite { field });
+        }
+        for (field, step) in [("reward_step", self.reward_step), ("punish_step", self.punish_step)] {
+            if step < 0.0 {
+                return Err(ConfigError::NegativeStep { field });
+            }
+        }
+        if !(self.floor <= self.neutral && self.neutral <= self.ceiling) {
+            return Err(ConfigError::Bounds {
+                floor: self.floor,
+                neutral: self.neutral,
+                ceiling: self.ceiling,
+            });
+        }
+        match self.weights.iter().find(|(_, w)| !(w.is_finite() && **w >= 0.0)) {
+            Some((offense, weight)) => Err(ConfigError::BadWeight { offense: *offense, weight: *weight }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn with_reward_step(mut self, step: f64) -> Self {
+        self.reward_step = step;
+        self
+    }
+
+    pub fn with_punish_step(mut self, step: f64) -> Self {
+        self.punish_step = step;
+        self
+    }
+
+    pub fn with_bounds(mut self, floor: f64, neutral: f64, ceiling: f64) -> Self {
+        self.floor = floor;
+        self.neutral = neutral;
+        self.ceiling = ceiling;
+        self
+    }
+
+    pub fn with_admit_threshold(mut self, threshold: f64) -> Self {
+        self.admit_threshold = threshold;
+        self
+    }
+
+    pub fn with_half_life_ms(mut self, half_life_ms: u64) -> Self {
+        self.half_life_ms = half_life_ms;
+        self
+    }
+
+    pub fn with_weight(mut self, offense: Offense, weight: f64) -> Self {
+        self.weights.insert(offense, weight);
+        self
+    }
+
+    pub fn weight(&self, offense: Offense) -> f64 {
+        self.weights.get(&offense).copied().unwrap_or(1.0)
+    }
+}
+
+/// A scoring rule. Implementations keep whatever per-peer state they
+/// need (a score, success/failure counts, a moving average...) and
+/// expose it as a score in [0, 1] that is compared against
+/// `admit_threshold`. Every call carries the current time, so a policy
+/// can let scores fade without a background task.
+pub trait ReputationPolicy {
+    /// Score of `who` as of `now`.
+    fn score(&self, who: &PubKey, now: Timestamp) -> f64;
+    fn reward(&mut self, who: &PubKey, now: Timestamp);
+    fn punish(&mut self, who: &PubKey, offense: Offense, now: Timestamp);
+    fn admit_threshold(&self) -> f64;
+    /// Every peer with state of its own, as last changed (others score
+    /// as neutral).
+    fn scores(&self) -> HashMap<PubKey, PeerScore>;
+    /// Force a score, e.g. when restoring saved state.
+    fn set(&mut self, who: &PubKey, score: PeerScore);
+}
+
+/// The default policy: fixed reward and (weighted) punishment steps,
+/// clamped to [floor, ceiling]. Between changes a score fades toward
+/// neutral from either side, halving its distance every `half_life_ms`:
+///   score(now) = neutral + (score - neutral) * 2^(-(now - at) / half_life)
+/// A step is applied to the faded score, so a peer idle for long starts
+/// again from (nearly) neutral.
+pub struct StepPolicy {
+    scores: HashMap<PubKey, PeerScore>,
+    config: ReputationConfig,
+}
+
+impl StepPolicy {
+    /// Fails if `config` does not pass `ReputationConfig::validate`.
+    pub fn new(config: ReputationConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self { scores: HashMap::new(), config })
+    }
+
+    pub fn config(&self) -> &ReputationConfig {
+        &self.config
+    }
+
+    fn faded(&self, s: &PeerScore, now: Timestamp) -> f64 {
+        let neutral = self.config.neutral;
+        if self.config.half_life_ms == 0 {
+            return s.score;
+        }
+        let elapsed = now.0.saturating_sub(s.at.0) as f64;
+        let halvings = elapsed / self.config.half_life_ms as f64;
+        neutral + (s.score - neutral) * 0.5f64.powf(halvings)
+    }
+
+    fn step(&mut self, who: &PubKey, delta: f64, now: Timestamp) {
+        let score = (self.score(who, now) + delta).clamp(self.config.floor, self.config.ceiling);
+        self.scores.insert(who.clone(), PeerScore { score, at: now });
+    }
+}
+
+impl ReputationPolicy for StepPolicy {
+    fn score(&self, who: &PubKey, now: Timestamp) -> f64 {
+        match self.scores.get(who) {
+            Some(s) => self.faded(s, now),
+            None => self.config.neutral,
+        }
+    }
+
+    fn reward(&mut self, who: &PubKey, now: Timestamp) {
+        self.step(who, self.config.reward_step, now);
+    }
+
+    fn punish(&mut self, who: &PubKey, offense: Offense, now: Timestamp) {
+        let step = self.config.punish_step * self.config.weight(offense);
+        self.step(who, -step, now);
+    }
+
+    fn admit_threshold(&self) -> f64 {
+        self.config.admit_threshold
+    }
+
+    fn scores(&self) -> HashMap<PubKey, PeerScore> {
+        self.scores.clone()
+    }
+
+    fn set(&mut self, who: &PubKey, score: PeerScore) {
+        let clamped = score.score.clamp(self.config.floor, self.config.ceiling);
+        self.scores.insert(who.clone(), PeerScore { score: clamped, ..score });
+    }
+}
+
+/// How peers' attestations are blended into local scores (see
+/// `ReputationBook::get_at`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrustConfig {
+    /// how far a score may move from the local one toward the attesters'
+    /// consensus: 0 ignores attestations, 1 could adopt it outright
+    pub weight: f64,
+    /// local score an attester needs before its attestations count
+    pub min_attester_score: f64,
+    /// messages we must have accepted from an attester before its
+    /// attestations count; a score alone is reached in a message or two
+    pub min_attester_accepted: u64,
+    /// most that attestations can raise a score above the local one
+    pub max_raise: f64,
+    /// most that attestations can lower a score below the local one
+    pub max_lower: f64,
+    /// attestations older than this are ignored; 0 keeps them forever
+    pub max_age_ms: u64,
+}
+
+impl Default for TrustConfig {
+    fn default() -> Self {
+        Self {
+            weight: 0.5,
+            min_attester_score: 0.6,
+            min_attester_accepted: 20,
+            max_raise: 0.1,
+            max_lower: 0.3,
+            max_age_ms: 7 * DEFAULT_HALF_LIFE_MS,
         }
     }
+}
+
+impl TrustConfig {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_min_attester_score(mut self, score: f64) -> Self {
+        self.min_attester_score = score;
+        self
+    }
+
+    pub fn with_min_attester_accepted(mut self, accepted: u64) -> Self {
+        self.min_attester_accepted = accepted;
+        self
+    }
+
+    pub fn with_max_raise(mut self, max_raise: f64) -> Self {
+        self.max_raise = max_raise;
+        self
+    }
 
+    pub fn with_max_lower(mut self, max_lower: f64) -> Self {
+        self.max_lower = max_lower;
+        self
+    }
+
+    pub fn with_max_age_ms(mut self, max_age_ms: u64) -> Self {
+        self.max_age_ms = max_age_ms;
+        self
+    }
+}
+
+/// The latest attestation taken from one attester.
+#[derive(Debug, Clone)]
+pub struct Attestation {
+    pub scores: HashMap<PubKey, f64>,
+    pub at: Timestamp,
+}
+
+/// Per-node view of how far each peer is trusted, backed by a
+/// `ReputationPolicy` (`StepPolicy` unless another one is plugged in).
+/// Every change is recorded as a `ReputationEvent`.
+///
+/// The policy keeps first-hand ("local") scores. Attestations from other
+/// peers are blended in when a score is read; they never change the
+/// local scores or the event log.
+pub struct ReputationBook {
+    policy: Box<dyn ReputationPolicy>,
+    events: VecDeque<ReputationEvent>,
+    // events ever recorded, including ones dropped from `events`
+    recorded: u64,
+    // latest attestation per attester
+    attestations: HashMap<PubKey, Attestation>,
+    // messages accepted per peer, for attester tenure
+    accepted: HashMap<PubKey, u64>,
+    trust: TrustConfig,
+}
+
+impl Default for ReputationBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReputationBook {
+    pub fn new() -> Self {
+        Self::with_config(ReputationConfig::default()).expect("default config is valid")
+    }
+
+    pub fn with_config(config: ReputationConfig) -> Result<Self, ConfigError> {
+        Ok(Self::with_policy(StepPolicy::new(config)?))
+    }
+
+    pub fn with_policy(policy: impl ReputationPolicy + 'static) -> Self {
+        Self {
+            policy: Box::new(policy),
+            events: VecDeque::new(),
+            recorded: 0,
+            attestations: HashMap::new(),
+            accepted: HashMap::new(),
+            trust: TrustConfig::default(),
+        }
+    }
+
+    pub fn with_trust(mut self, trust: TrustConfig) -> Self {
+        self.trust = trust;
+        self
+    }
+
+    pub fn trust(&self) -> &TrustConfig {
+        &self.trust
+    }
+
+    /// Current score of `who`, attestations included.
     pub fn get(&self, who: &PubKey) -> f64 {
-        *self.scores.get(who).unwrap_or(&self.neutral)
+        self.get_at(who, now_timestamp())
     }
 
-    pub fn reward(&mut self, who: &PubKey) {
-        let e = self.scores.entry(who.clone()).or_insert(self.neutral);
-        *e = (*e + self.reward_step).min(self.ceiling);
+    /// Score of `who` as of `now`, e.g. to see where an idle peer will be.
+    ///
+    /// The local score is pulled toward the attesters' consensus
+    ///   consensus = sum_a w_a c_a / sum_a w_a,   w_a = local(a)
+    ///   score = local + weight * W / (W + 1) * (consensus - local)
+    /// where c_a is attester a's claim and W = sum_a w_a, so a single
+    /// attester moves a score less than several agreeing ones. Against
+    /// collusion:
+    /// - only attesters with a local score of at least
+    ///   `min_attester_score` and at least `min_attester_accepted`
+    ///   messages accepted count; the score alone is no bar, since a
+    ///   fresh identity passes it after a message or two,
+    /// - attesters are weighted by local scores only, never blended
+    ///   ones, so vouching does not chain and a ring of peers vouching
+    ///   for each other gains nothing it had not earned first-hand,
+    /// - each attester counts once (its latest attestation), and never
+    ///   about itself,
+    /// - attestations raise a score by at most `max_raise` and lower it by
+    ///   at most `max_lower`.
+    pub fn get_at(&self, who: &PubKey, now: Timestamp) -> f64 {
+        let local = self.local_at(who, now);
+        let t = &self.trust;
+        let (mut mass, mut sum) = (0.0, 0.0);
+        for (attester, att) in &self.attestations {
+            if attester == who {
+                continue;
+            }
+            if t.max_age_ms > 0 && now.0.saturating_sub(att.at.0) > t.max_age_ms as u128 {
+                continue;
+            }
+            let claim = match att.scores.get(who) {
+                Some(c) => *c,
+                None => continue,
+            };
+            let w = self.local_at(attester, now);
+            if w < t.min_attester_score || self.accepted(attester) < t.min_attester_accepted {
+                continue;
+            }
+            mass += w;
+            sum += w * claim;
+        }
+        if mass == 0.0 {
+            return local;
+        }
+        let consensus = sum / mass;
+        let blended = local + t.weight * mass / (mass + 1.0) * (consensus - local);
+        blended.clamp(local - t.max_lower, local + t.max_raise)
     }
 
-    pub fn punish(&mut self, who: &PubKey) {
-        let e = self.scores.entry(who.clone()).or_insert(self.neutral);
-        *e = (*e - self.punish_step).max(self.floor);
+    /// Count one more message accepted from `who`, toward its tenure as
+    /// an attester.
+    pub fn note_accepted(&mut self, who: &PubKey) {
+        *self.accepted.entry(who.clone()).or_default() += 1;
     }
 
-    pub fn decay(&mut self) {
-        let neutral = self.neutral;
-        for (_, score) in self.scores.iter_mut() {
-            if *score < neutral {
-                let delta = 0.1 * (neutral - *score);
-                *score += delta;
+    /// Messages accepted from `who` so far.
+    pub fn accepted(&self, who: &PubKey) -> u64 {
+        self.accepted.get(who).copied().unwrap_or(0)
+    }
+
+    /// First-hand score of `who`, without attestations.
+    pub fn local(&self, who: &PubKey) -> f64 {
+        self.local_at(who, now_timestamp())
+    }
+
+    pub fn local_at(&self, who: &PubKey, now: Timestamp) -> f64 {
+        self.policy.score(who, now)
+    }
+
+    /// Current score of every peer with a score of its own or attested
+    /// by someone (others are at neutral).
+    pub fn scores(&self) -> HashMap<PubKey, f64> {
+        let now = now_timestamp();
+        let mut peers: HashSet<PubKey> = self.policy.scores().into_keys().collect();
+        for att in self.attestations.values() {
+            peers.extend(att.scores.keys().cloned());
+        }
+        peers
+            .into_iter()
+            .map(|who| {
+                let score = self.get_at(&who, now);
+                (who, score)
+            })
+            .collect()
+    }
+
+    /// Take `attester`'s scores of other peers as of `at`, replacing its
+    /// previous attestation. An attestation no newer than the one held,
+    /// or dated more than `MAX_CLOCK_SKEW_MS` in the future, is ignored
+    /// and false returned. Scores about the attester itself are dropped.
+    pub fn attest(&mut self, attester: &PubKey, scores: &[(PubKey, f64)], at: Timestamp) -> bool {
+        if at.0 > now_timestamp().0.saturating_add(MAX_CLOCK_SKEW_MS) {
+            return false;
+        }
+        if let Some(old) = self.attestations.get(attester) {
+            if old.at >= at {
+                return false;
+            }
+        }
+        let scores = scores
+            .iter()
+            .filter(|(who, _)| who != attester)
+            .map(|(who, s)| (who.clone(), s.clamp(0.0, 1.0)))
+            .collect();
+        self.attestations.insert(attester.clone(), Attestation { scores, at });
+        true
+    }
+
+    /// The attestation held from `attester`, if any.
+    pub fn attestation(&self, attester: &PubKey) -> Option<&Attestation> {
+        self.attestations.get(attester)
+    }
+
+    /// Scores as last changed, for saving (see `restore`).
+    pub fn saved_scores(&self) -> HashMap<PubKey, PeerScore> {
+        self.policy.scores()
+    }
+
+    /// Overwrite one score as of now.
+    pub fn set(&mut self, who: &PubKey, score: f64) {
+        self.restore(who, PeerScore { score, at: now_timestamp() });
+    }
+
+    /// Put back a saved score; it keeps fading from when it was saved.
+    pub fn restore(&mut self, who: &PubKey, score: PeerScore) {
+        self.policy.set(who, score);
+    }
+
+    /// Reward `who` for `digest` (the accepted message).
+    pub fn reward(&mut self, who: &PubKey, digest: Option<&Digest>) {
+        let now = now_timestamp();
+        let before = self.local_at(who, now);
+        self.policy.reward(who, now);
+        self.record(who, before, EventReason::Accepted, digest, now);
+    }
+
+    pub fn punish(&mut self, who: &PubKey, offense: Offense, digest: Option<&Digest>) {
+        let now = now_timestamp();
+        let before = self.local_at(who, now);
+        self.policy.punish(who, offense, now);
+        self.record(who, before, EventReason::Punished(offense), digest, now);
+    }
+
+    fn record(
+        &mut self,
+        who: &PubKey,
+        before: f64,
+        reason: EventReason,
+        digest: Option<&Digest>,
+        at: Timestamp,
+    ) {
+        if self.events.len() == EVENT_LOG_CAP {
+            self.events.pop_front();
+        }
+        self.events.push_back(ReputationEvent {
+            who: who.clone(),
+            delta: self.local_at(who, at) - before,
+            reason,
+            digest: digest.cloned(),
+            at,
+        });
+        self.recorded += 1;
+    }
+
+    /// Retained events, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = &ReputationEvent> {
+        self.events.iter()
+    }
+
+    /// Retained events about `who`, oldest first.
+    pub fn history(&self, who: &PubKey) -> Vec<&ReputationEvent> {
+        self.events.iter().filter(|e| e.who == *who).collect()
+    }
+
+    /// Number of events ever recorded (retained or not).
+    pub fn recorded(&self) -> u64 {
+        self.recorded
+    }
+
+    /// Retained events recorded after the first `seen` ones.
+    pub fn events_since(&self, seen: u64) -> Vec<ReputationEvent> {
+        let new = self.recorded.saturating_sub(seen) as usize;
+        let skip = self.events.len().saturating_sub(new);
+        self.events.iter().skip(skip).cloned().collect()
+    }
+
+    /// Put back saved events (e.g. on restart); they count as recorded.
+    pub fn restore_events(&mut self, events: impl IntoIterator<Item = ReputationEvent>) {
+        for e in events {
+            if self.events.len() == EVENT_LOG_CAP {
+                self.events.pop_front();
             }
+            self.events.push_back(e);
+            self.recorded += 1;
         }
     }
 
+    pub fn export(&self) -> ReputationExport {
+        let mut scores: Vec<(PubKey, f64)> = self.scores().into_iter().collect();
+        scores.sort_by_key(|(who, _)| who.0);
+        ReputationExport {
+            scores,
+            events: self.events.iter().cloned().collect(),
+        }
+    }
+
+    pub fn export_json(&self) -> String {
+        serde_json::to_string_pretty(&self.export()).expect("reputation export is serializable")
+    }
+
     pub fn admit_threshold(&self) -> f64 {
-        self.admit_threshold
+        self.policy.admit_threshold()
     }
 }
@@ -0,0 +1,192 @@
+//! Retina solve: fit basis coefficients to foveated samples.
+//!
+//! The scene on the unit square is modelled as
+//! `f(x, y) = sum_k a_k * phi_k(x, y)` with the separable cosine basis
+//! `phi_{i,j}(x, y) = cos(pi * i * x) * cos(pi * j * y)`, `i < nx`,
+//! `j < ny`, flattened row-major as `k = j * nx + i`.
+//!
+//! Each sample `(x, y, value)` is weighted by the foveation Gaussian
+//! `w = exp(-((x - cx)^2 + (y - cy)^2) / (2 * sigma^2))`, and `a_hat`
+//! minimizes `sum_i w_i (f(x_i, y_i) - value_i)^2 + lambda * |a|^2`.
+
+use std::f64::consts::PI;
+
+/// PSNR reported for a (numerically) exact fit.
+pub const PSNR_CAP_DB: f64 = 100.0;
+
+/// Smallest ridge we ever solve with, so duplicate samples cannot make
+/// the system singular.
+const MIN_LAMBDA: f64 = 1e-9;
+
+/// Result of one solve.
+#[derive(Debug, Clone)]
+pub struct RetinaSolve {
+    pub a_hat: Vec<f64>,
+    /// 10 log10(peak^2 / mse) of the fit residual over the samples
+    pub psnr_equiv_db: f64,
+    /// mean foveation weight of the samples, in [0, 1]
+    pub alignment: f64,
+}
+
+pub fn basis_fingerprint(nx: u32, ny: u32) -> String {
+    format!("basis/cos2d/{}x{}", nx, ny)
+}
+
+/// Values of all `nx * ny` basis functions at `(x, y)`.
+pub fn basis_row(nx: u32, ny: u32, x: f64, y: f64) -> Vec<f64> {
+    let cx: Vec<f64> = (0..nx).map(|i| (PI * i as f64 * x).cos()).collect();
+    let mut row = Vec::with_capacity((nx * ny) as usize);
+    for j in 0..ny {
+        let cy = (PI * j as f64 * y).cos();
+        row.extend(cx.iter().map(|c| c * cy));
+    }
+    row
+}
+
+pub fn foveation_weight(sigma: f64, center: (f64, f64), x: f64, y: f64) -> f64 {
+    let d2 = (x - center.0).powi(2) + (y - center.1).powi(2);
+    if sigma <= 0.0 {
+        return if d2 == 0.0 { 1.0 } else { 0.0 };
+    }
+    (-d2 / (2.0 * sigma * sigma)).exp()
+}
+
+/// Ridge-regularized weighted least squares of `samples` on the basis.
+///
+/// With `B = sqrt(W) A` and `z = sqrt(W) y`, we solve whichever of
+/// `(B^T B + lambda I) a = B^T z` (primal, n x n) or
+/// `a = B^T (B B^T + lambda I)^-1 z` (dual, m x m) is smaller; both give
+/// the same `a_hat`.
+// Dense linear algebra reads clearer with explicit indices.
+#[allow(clippy::needless_range_loop)]
+pub fn solve(
+    samples: &[(f64, f64, f64)],
+    lambda: f64,
+    sigma: f64,
+    center: (f64, f64),
+    nx: u32,
+    ny: u32,
+) -> RetinaSolve {
+    let n = (nx * ny) as usize;
+    let m = samples.len();
+    if n == 0 || m == 0 {
+        return RetinaSolve { a_hat: vec![0.0; n], psnr_equiv_db: 0.0, alignment: 0.0 };
+    }
+    let lambda = if lambda.is_finite() { lambda.max(MIN_LAMBDA) } else { MIN_LAMBDA };
+
+    let weights: Vec<f64> = samples
+        .iter()
+        .map(|(x, y, _)| foveation_weight(sigma, center, *x, *y))
+        .collect();
+    let rows: Vec<Vec<f64>> = samples
+        .iter()
+        .zip(&weights)
+        .map(|((x, y, _), w)| {
+            let sw = w.sqrt();
+            basis_row(nx, ny, *x, *y).into_iter().map(|v| v * sw).collect()
+        })
+        .collect();
+    let z: Vec<f64> = samples
+        .iter()
+        .zip(&weights)
+        .map(|((_, _, v), w)| v * w.sqrt())
+        .collect();
+
+    let a_hat = if n <= m {
+        let mut g = vec![vec![0.0; n]; n];
+        let mut rhs = vec![0.0; n];
+        for (row, zi) in rows.iter().zip(&z) {
+            for p in 0..n {
+                rhs[p] += row[p] * zi;
+                for q in 0..=p {
+                    g[p][q] += row[p] * row[q];
+                }
+            }
+        }
+        for p in 0..n {
+            g[p][p] += lambda;
+            for q in 0..p {
+                g[q][p] = g[p][q];
+            }
+        }
+        cholesky_solve(g, rhs)
+    } else {
+        let mut g = vec![vec![0.0; m]; m];
+        for p in 0..m {
+            for q in 0..=p {
+                let dot: f64 = rows[p].iter().zip(&rows[q]).map(|(u, v)| u * v).sum();
+                g[p][q] = dot;
+                g[q][p] = dot;
+            }
+            g[p][p] += lambda;
+        }
+        let alpha = cholesky_solve(g, z);
+        let mut a = vec![0.0; n];
+        for (row, al) in rows.iter().zip(&alpha) {
+            for (ak, r) in a.iter_mut().zip(row) {
+                *ak += r * al;
+            }
+        }
+        a
+    };
+
+    let mut sse = 0.0;
+    let (mut lo, mut hi) = (f64::INFINITY, f64::NEG_INFINITY);
+    for (x, y, v) in samples {
+        let fit: f64 = basis_row(nx, ny, *x, *y)
+            .iter()
+            .zip(&a_hat)
+            .map(|(b, a)| b * a)
+            .sum();
+        sse += (fit - v).powi(2);
+        lo = lo.min(*v);
+        hi = hi.max(*v);
+    }
+    let mse = sse / m as f64;
+    let peak = if hi - lo > 0.0 { hi - lo } else { hi.abs().max(1.0) };
+    let psnr_equiv_db = if mse <= 0.0 {
+        PSNR_CAP_DB
+    } else {
+        (10.0 * (peak * peak / mse).log10()).clamp(0.0, PSNR_CAP_DB)
+    };
+    let alignment = weights.iter().sum::<f64>() / m as f64;
+
+    RetinaSolve { a_hat, psnr_equiv_db, alignment }
+}
+
+/// Solve `g x = b` for symmetric positive definite `g`.
+#[allow(clippy::needless_range_loop)]
+fn cholesky_solve(mut g: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    // in-place lower factor L with g = L L^T
+    for j in 0..n {
+        let mut d = g[j][j];
+        for k in 0..j {
+            d -= g[j][k] * g[j][k];
+        }
+        let d = d.max(f64::MIN_POSITIVE).sqrt();
+        g[j][j] = d;
+        for i in (j + 1)..n {
+            let mut s = g[i][j];
+            for k in 0..j {
+                s -= g[i][k] * g[j][k];
+            }
+            g[i][j] = s / d;
+        }
+    }
+    // forward: L y = b
+    for i in 0..n {
+        for k in 0..i {
+            b[i] -= g[i][k] * b[k];
+        }
+        b[i] /= g[i][i];
+    }
+    // backward: L^T x = y
+    for i in (0..n).rev() {
+        for k in (i + 1)..n {
+            b[i] -= g[k][i] * b[k];
+        }
+        b[i] /= g[i][i];
+    }
+    b
+}
@@ -1,33 +1,421 @@
+use std::collections::HashMap;
 use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
-use crate::types::Digest;
+use serde::{Serialize, Deserialize};
 
-fn cas_dir() -> PathBuf {
-    PathBuf::from(".cas")
+use sha2::{Digest as ShaDigest, Sha256};
+
+use crate::canonical;
+use crate::types::{Digest, PubKey};
+
+/// Chunk boundaries are content-defined (gear rolling hash), so an edit
+/// in the middle of a file only changes the chunks around the edit and
+/// every other chunk is deduplicated on resend.
+pub const MIN_CHUNK: usize = 16 * 1024;
+pub const MAX_CHUNK: usize = 256 * 1024;
+/// A boundary is cut when the top 16 bits of the rolling hash are zero,
+/// giving ~64 KiB average chunks after MIN_CHUNK.
+const CUT_MASK: u64 = 0xffff_0000_0000_0000;
+
+const MANIFEST_MAGIC: &[u8] = b"collapse-manifest/v1\n";
+
+/// One chunk of a chunked blob.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRef {
+    pub digest: Digest,
+    pub len: u64,
+}
+
+/// Object listing the chunks of a blob in order. Its own digest is the
+/// blob's `object_digest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub total_len: u64,
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl Manifest {
+    /// Canonical encoding, so every implementation that chunks a blob the
+    /// same way arrives at the same `object_digest`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MANIFEST_MAGIC.to_vec();
+        out.extend_from_slice(&canonical::to_bytes(self).expect("manifest encode"));
+        out
+    }
+
+    /// `None` if `bytes` is a plain object rather than a manifest.
+    pub fn parse(bytes: &[u8]) -> Option<Manifest> {
+        let body = bytes.strip_prefix(MANIFEST_MAGIC)?;
+        canonical::from_bytes(body).ok()
+    }
+}
+
+/// Content-addressed object storage. Objects are immutable byte strings
+/// addressed by `object_digest`; chunked blobs are built on top of this
+/// by the free functions below.
+pub trait BlobStore {
+    /// Store one raw object (a chunk, a manifest, or a small blob).
+    fn put(&mut self, bytes: &[u8]) -> io::Result<Digest>;
+
+    /// Fetch one raw object. Implementations rehash what they read and
+    /// fail with `InvalidData` rather than return corrupt bytes.
+    fn get(&self, digest: &Digest) -> io::Result<Vec<u8>>;
+
+    fn has(&self, digest: &Digest) -> bool;
+
+    /// Remove an object; removing a missing object is not an error.
+    fn delete(&mut self, digest: &Digest) -> io::Result<()>;
+
+    /// Every object digest currently stored.
+    fn list(&self) -> io::Result<Vec<Digest>>;
+
+    /// Stored size of an object in bytes.
+    fn size(&self, digest: &Digest) -> io::Result<u64> {
+        self.get(digest).map(|b| b.len() as u64)
+    }
+
+    /// Move a corrupt object out of the way. Stores without a place to
+    /// keep it for inspection just delete it.
+    fn quarantine(&mut self, digest: &Digest) -> io::Result<()> {
+        self.delete(digest)
+    }
+}
+
+/// Result of an integrity scan over a whole store.
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    pub checked: usize,
+    pub corrupt: Vec<Digest>,
+    pub quarantined: bool,
+}
+
+/// `bytes` as read back for `digest`, or `InvalidData` if they no
+/// longer hash to it.
+pub fn verify_object(digest: &Digest, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    if object_digest(&bytes) != *digest {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("object {} is corrupt", digest_to_hex(digest)),
+        ));
+    }
+    Ok(bytes)
 }
 
-fn digest_to_hex(d: &Digest) -> String {
+/// Rehash every object in `store`. Corrupt objects are reported and,
+/// if `quarantine` is set, moved aside so they can be fetched again.
+pub fn fsck(store: &mut dyn BlobStore, quarantine: bool) -> io::Result<FsckReport> {
+    let mut report = FsckReport { quarantined: quarantine, ..FsckReport::default() };
+    for digest in store.list()? {
+        report.checked += 1;
+        match store.get(&digest) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                report.corrupt.push(digest);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if quarantine {
+        for digest in &report.corrupt {
+            store.quarantine(digest)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Address of an object in the CAS: plain SHA-256 of its bytes, so
+/// chunks can be hashed without any encoding step.
+pub fn object_digest(bytes: &[u8]) -> Digest {
+    let out = Sha256::digest(bytes);
+    let mut d = [0u8; 32];
+    d.copy_from_slice(&out);
+    Digest(d)
+}
+
+pub fn digest_to_hex(d: &Digest) -> String {
     d.0.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-pub fn put(bytes: &[u8]) -> io::Result<Digest> {
-    use crate::types::compute_digest;
+pub fn digest_from_hex(s: &str) -> Option<Digest> {
+    if s.len() != 64 {
+        return None;
+    }
+    let mut d = [0u8; 32];
+    for (i, byte) in d.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(Digest(d))
+}
+
+/// Filesystem store: one file per object, named by its hex digest,
+/// under a configurable root directory. Corrupt objects are moved to
+/// `<root>/quarantine/`.
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl Default for FsStore {
+    /// The historical `.cas` directory in the working directory.
+    fn default() -> Self {
+        Self::new(".cas")
+    }
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// A store of `id`'s own under `.cas`, so that one node's gc never
+    /// sweeps objects another node in the same directory still needs.
+    pub fn for_node(id: &PubKey) -> Self {
+        Self::new(Path::new(".cas").join(id.to_string()))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path_of(&self, digest: &Digest) -> PathBuf {
+        self.root.join(digest_to_hex(digest))
+    }
+}
+
+impl BlobStore for FsStore {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<Digest> {
+        let digest = object_digest(bytes);
+        fs::create_dir_all(&self.root)?;
+        let path = self.path_of(&digest);
+        // an existing copy is only kept if it is still intact
+        if !path.exists() || self.get(&digest).is_err() {
+            // write-then-rename so readers never see a partial object
+            let tmp = self.root.join(format!(
+                "{}.tmp{}",
+                digest_to_hex(&digest),
+                rand::random::<u32>()
+            ));
+            fs::write(&tmp, bytes)?;
+            fs::rename(&tmp, &path)?;
+        }
+        Ok(digest)
+    }
+
+    fn get(&self, digest: &Digest) -> io::Result<Vec<u8>> {
+        verify_object(digest, fs::read(self.path_of(digest))?)
+    }
+
+    fn has(&self, digest: &Digest) -> bool {
+        self.path_of(digest).exists()
+    }
+
+    fn delete(&mut self, digest: &Digest) -> io::Result<()> {
+        match fs::remove_file(self.path_of(digest)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<Digest>> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(e) => e,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut out = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            if let Some(d) = entry.file_name().to_str().and_then(digest_from_hex) {
+                out.push(d);
+            }
+        }
+        Ok(out)
+    }
+
+    fn size(&self, digest: &Digest) -> io::Result<u64> {
+        fs::metadata(self.path_of(digest)).map(|m| m.len())
+    }
+
+    fn quarantine(&mut self, digest: &Digest) -> io::Result<()> {
+        let dir = self.root.join("quarantine");
+        fs::create_dir_all(&dir)?;
+        fs::rename(self.path_of(digest), dir.join(digest_to_hex(digest)))
+    }
+}
+
+/// In-memory store, for tests and ephemeral nodes.
+#[derive(Default)]
+pub struct MemStore {
+    objects: HashMap<Digest, Vec<u8>>,
+}
+
+impl MemStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BlobStore for MemStore {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<Digest> {
+        let digest = object_digest(bytes);
+        self.objects.entry(digest.clone()).or_insert_with(|| bytes.to_vec());
+        Ok(digest)
+    }
+
+    fn get(&self, digest: &Digest) -> io::Result<Vec<u8>> {
+        let bytes = self
+            .objects
+            .get(digest)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "object not in store"))?;
+        verify_object(digest, bytes)
+    }
+
+    fn has(&self, digest: &Digest) -> bool {
+        self.objects.contains_key(digest)
+    }
+
+    fn delete(&mut self, digest: &Digest) -> io::Result<()> {
+        self.objects.remove(digest);
+        Ok(())
+    }
 
-    let digest = compute_digest(&bytes);
-    let dir = cas_dir();
-    fs::create_dir_all(&dir)?;
-    let path = dir.join(digest_to_hex(&digest));
-    if !path.exists() {
-        fs::write(&path, bytes)?;
+    fn list(&self) -> io::Result<Vec<Digest>> {
+        Ok(self.objects.keys().cloned().collect())
     }
-    Ok(digest)
 }
 
-pub fn get(digest: &Digest) -> io::Result<Vec<u8>> {
-    let dir = cas_dir();
-    let path = dir.join(digest_to_hex(digest));
-    let data = fs::read(path)?;
-    Ok(data)
+/// Chunk `reader` into the CAS and return the digest of its manifest.
+/// Only one chunk is held in memory at a time.
+pub fn put_stream<R: Read>(store: &mut dyn BlobStore, reader: R) -> io::Result<Digest> {
+    let mut chunker = Chunker::new(reader);
+    let mut manifest = Manifest { total_len: 0, chunks: Vec::new() };
+    while let Some(chunk) = chunker.next_chunk()? {
+        let digest = store.put(&chunk)?;
+        manifest.total_len += chunk.len() as u64;
+        manifest.chunks.push(ChunkRef { digest, len: chunk.len() as u64 });
+    }
+    store.put(&manifest.to_bytes())
+}
+
+/// Chunked put of an in-memory blob.
+pub fn put_blob(store: &mut dyn BlobStore, bytes: &[u8]) -> io::Result<Digest> {
+    put_stream(store, bytes)
+}
+
+/// Write the blob stored under `digest` into `writer`, chunk by chunk.
+/// Plain (unchunked) objects are written as-is. Returns bytes written.
+pub fn get_stream<W: Write>(
+    store: &dyn BlobStore,
+    digest: &Digest,
+    mut writer: W,
+) -> io::Result<u64> {
+    let object = store.get(digest)?;
+    let manifest = match Manifest::parse(&object) {
+        Some(m) => m,
+        None => {
+            writer.write_all(&object)?;
+            writer.flush()?;
+            return Ok(object.len() as u64);
+        }
+    };
+    let mut written = 0u64;
+    for c in &manifest.chunks {
+        let chunk = store.get(&c.digest)?;
+        writer.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Whole-blob read for callers that want the bytes in memory.
+pub fn get_blob(store: &dyn BlobStore, digest: &Digest) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    get_stream(store, digest, &mut out)?;
+    Ok(out)
+}
+
+/// Chunks of the blob under `digest` that are not in the CAS yet
+/// (empty if the manifest itself is missing or the object is plain).
+pub fn missing_chunks(store: &dyn BlobStore, digest: &Digest) -> Vec<Digest> {
+    let manifest = match store.get(digest).ok().and_then(|b| Manifest::parse(&b)) {
+        Some(m) => m,
+        None => return Vec::new(),
+    };
+    manifest
+        .chunks
+        .into_iter()
+        .map(|c| c.digest)
+        .filter(|d| !store.has(d))
+        .collect()
+}
+
+/// Gear table: 256 pseudo-random words from a fixed SplitMix64 stream,
+/// so chunk boundaries are identical on every build and platform.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x636f_6c6c_6170_7365; // "collapse"
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Length of the first chunk of `data`.
+fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK);
+    let mut h: u64 = 0;
+    for (i, b) in data.iter().enumerate().take(end).skip(MIN_CHUNK) {
+        h = (h << 1).wrapping_add(GEAR[*b as usize]);
+        if h & CUT_MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+struct Chunker<R> {
+    inner: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, buf: Vec::with_capacity(MAX_CHUNK), eof: false }
+    }
+
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut tmp = vec![0u8; 64 * 1024];
+        while !self.eof && self.buf.len() < MAX_CHUNK {
+            match self.inner.read(&mut tmp) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.buf.extend_from_slice(&tmp[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let cut = cut_point(&self.buf);
+        let rest = self.buf.split_off(cut);
+        Ok(Some(std::mem::replace(&mut self.buf, rest)))
+    }
 }
@@ -1,15 +1,41 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use crate::types::PubKey;
-use crate::content::Message;
+use crate::wire::Frame;
 
-// Transport is how nodes send messages to peers.
-// Each NodeMessenger will hold a Box<dyn Transport>.
+// Transport is how nodes send frames (messages and sync traffic) to peers.
+// Each NodeMessenger holds a Box<dyn Transport>.
 pub trait Transport {
-    // send one canonical message to a specific peer identity
-    fn send_to(&mut self, to: &PubKey, msg: &Message);
+    // called once by NodeMessenger::new so the transport can route to `me`
+    fn register(&mut self, _me: &PubKey) {}
+
+    // send one frame to a specific peer identity
+    fn send_to(&mut self, to: &PubKey, frame: &Frame);
+
+    // broadcast one frame to all known peers
+    fn broadcast(&mut self, from: &PubKey, frame: &Frame);
+
+    // (pull) get all inbound frames destined for `me`
+    fn drain_inbound(&mut self, me: &PubKey) -> Vec<Frame>;
+}
+
+// A shared bus (e.g. one MemoryTransport for several in-process nodes)
+// is handed to each node as an Rc<RefCell<...>> handle.
+impl<T: Transport + ?Sized> Transport for Rc<RefCell<T>> {
+    fn register(&mut self, me: &PubKey) {
+        self.borrow_mut().register(me);
+    }
+
+    fn send_to(&mut self, to: &PubKey, frame: &Frame) {
+        self.borrow_mut().send_to(to, frame);
+    }
 
-    // broadcast one canonical message to all known peers
-    fn broadcast(&mut self, from: &PubKey, msg: &Message);
+    fn broadcast(&mut self, from: &PubKey, frame: &Frame) {
+        self.borrow_mut().broadcast(from, frame);
+    }
 
-    // (pull) get all inbound messages destined for `me`
-    fn drain_inbound(&mut self, me: &PubKey) -> Vec<Message>;
+    fn drain_inbound(&mut self, me: &PubKey) -> Vec<Frame> {
+        self.borrow_mut().drain_inbound(me)
+    }
 }
@@ -1,15 +1,21 @@
 use std::collections::{HashMap, HashSet};
 use crate::types::PubKey;
-use crate::content::Message;
+use crate::wire::Frame;
 use crate::transport::Transport;
 
-/// MemoryTransport is a shared in-memory message bus.
+/// MemoryTransport is a shared in-memory frame bus.
 /// Each registered PubKey gets a queue. send_to() enqueues to one.
 /// broadcast() enqueues to all peers except the sender.
-/// drain_inbound() hands a node its queued messages.
+/// drain_inbound() hands a node its queued frames.
 pub struct MemoryTransport {
     peers: HashSet<PubKey>,
-    queues: HashMap<PubKey, Vec<Message>>,
+    queues: HashMap<PubKey, Vec<Frame>>,
+}
+
+impl Default for MemoryTransport {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryTransport {
@@ -22,24 +28,28 @@ impl MemoryTransport {
 
     pub fn register_peer(&mut self, who: PubKey) {
         self.peers.insert(who.clone());
-        self.queues.entry(who).or_insert_with(Vec::new);
+        self.queues.entry(who).or_default();
     }
 
-    fn enqueue(&mut self, to: &PubKey, msg: &Message) {
+    fn enqueue(&mut self, to: &PubKey, frame: &Frame) {
         if let Some(q) = self.queues.get_mut(to) {
-            q.push(msg.clone());
+            q.push(frame.clone());
         }
     }
 }
 
 impl Transport for MemoryTransport {
-    fn send_to(&mut self, to: &PubKey, msg: &Message) {
+    fn register(&mut self, me: &PubKey) {
+        self.register_peer(me.clone());
+    }
+
+    fn send_to(&mut self, to: &PubKey, frame: &Frame) {
         if self.peers.contains(to) {
-            self.enqueue(to, msg);
+            self.enqueue(to, frame);
         }
     }
 
-    fn broadcast(&mut self, from: &PubKey, msg: &Message) {
+    fn broadcast(&mut self, from: &PubKey, frame: &Frame) {
         // Step 1: snapshot peers so we don't alias-borrow self.peers
         // while mutating self.queues.
         let targets: Vec<PubKey> = self
@@ -51,11 +61,11 @@ impl Transport for MemoryTransport {
 
         // Step 2: now it's safe to mutate self.queues
         for peer_id in targets {
-            self.enqueue(&peer_id, msg);
+            self.enqueue(&peer_id, frame);
         }
     }
 
-    fn drain_inbound(&mut self, me: &PubKey) -> Vec<Message> {
+    fn drain_inbound(&mut self, me: &PubKey) -> Vec<Frame> {
         if let Some(q) = self.queues.get_mut(me) {
             let drained = q.clone();
             q.clear();
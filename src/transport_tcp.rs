@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::transport::Transport;
+use crate::types::PubKey;
+use crate::wire::{decode_frame, encode_frame, Frame};
+
+/// Frames larger than this are treated as a protocol error and the
+/// connection is dropped. The largest legitimate frames are blob
+/// responses: a chunk of at most `MAX_CHUNK` bytes, which JSON spells out
+/// as up to four characters per byte, or the manifest of a multi-GiB blob.
+pub const MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// Inbound connections served at once; further ones are closed on accept.
+pub const MAX_INBOUND_CONNECTIONS: usize = 64;
+
+/// Decoded frames waiting for drain_inbound(). When the queue is full,
+/// readers stop reading and TCP flow control pushes back on the senders.
+pub const INBOUND_QUEUE_CAP: usize = 1024;
+
+/// How long connecting to, or writing one frame to, a peer may take
+/// before the peer counts as unreachable. Sends run on the node's own
+/// thread, so a stalled peer must not hold it up for long.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// TcpTransport carries frames between processes over TCP.
+/// Each frame is its wire encoding prefixed by a u32 big-endian length.
+/// Outbound connections are opened lazily per peer and re-opened when
+/// they fail; inbound connections are served by background threads that
+/// push decoded frames into a bounded queue drained by drain_inbound().
+pub struct TcpTransport {
+    local_addr: SocketAddr,
+    book: HashMap<PubKey, SocketAddr>,
+    conns: HashMap<PubKey, TcpStream>,
+    inbound: Receiver<Frame>,
+    // open inbound connections by id, removed when their reader exits
+    accepted: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    shutdown: Arc<AtomicBool>,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+}
+
+impl TcpTransport {
+    /// Listen on `addr` (use port 0 to let the OS pick one).
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let (tx, inbound) = mpsc::sync_channel(INBOUND_QUEUE_CAP);
+        let accepted = Arc::new(Mutex::new(HashMap::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        {
+            let accepted = accepted.clone();
+            let shutdown = shutdown.clone();
+            thread::spawn(move || accept_loop(listener, tx, accepted, shutdown));
+        }
+
+        Ok(Self {
+            local_addr,
+            book: HashMap::new(),
+            conns: HashMap::new(),
+            inbound,
+            accepted,
+            shutdown,
+            connect_timeout: CONNECT_TIMEOUT,
+            write_timeout: WRITE_TIMEOUT,
+        })
+    }
+
+    /// Override `CONNECT_TIMEOUT` and `WRITE_TIMEOUT`.
+    pub fn with_timeouts(mut self, connect: Duration, write: Duration) -> Self {
+        self.connect_timeout = connect;
+        self.write_timeout = write;
+        self
+    }
+
+    /// Number of inbound connections currently open.
+    pub fn inbound_connections(&self) -> usize {
+        self.accepted.lock().expect("accepted list poisoned").len()
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Address book entry: where to reach `who`.
+    pub fn add_peer(&mut self, who: PubKey, addr: SocketAddr) {
+        self.conns.remove(&who);
+        self.book.insert(who, addr);
+    }
+
+    fn connection(&mut self, to: &PubKey) -> io::Result<&mut TcpStream> {
+        let alive = self.conns.get(to).map(is_alive).unwrap_or(false);
+        if !alive {
+            let addr = *self
+                .book
+                .get(to)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unknown peer"))?;
+            let stream = TcpStream::connect_timeout(&addr, self.connect_timeout)?;
+            stream.set_nodelay(true)?;
+            stream.set_write_timeout(Some(self.write_timeout))?;
+            self.conns.insert(to.clone(), stream);
+        }
+        Ok(self.conns.get_mut(to).expect("connection just inserted"))
+    }
+
+    /// Write one frame, reconnecting once if the cached connection broke.
+    /// A fresh connection that fails is not retried: the peer is down or
+    /// stalled, and another attempt would only block as long again.
+    fn deliver(&mut self, to: &PubKey, payload: &[u8]) -> io::Result<()> {
+        let cached = self.conns.contains_key(to);
+        let first = self.connection(to).and_then(|s| write_frame(s, payload));
+        if first.is_ok() {
+            return Ok(());
+        }
+        self.conns.remove(to);
+        if !cached {
+            return first;
+        }
+        self.connection(to).and_then(|s| write_frame(s, payload))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send_to(&mut self, to: &PubKey, frame: &Frame) {
+        let payload = encode_frame(frame);
+        if let Err(e) = self.deliver(to, payload.as_bytes()) {
+            self.conns.remove(to);
+            eprintln!("tcp send to {} failed: {}", to, e);
+        }
+    }
+
+    fn broadcast(&mut self, from: &PubKey, frame: &Frame) {
+        let targets: Vec<PubKey> = self
+            .book
+            .keys()
+            .filter(|p| *p != from)
+            .cloned()
+            .collect();
+
+        for peer_id in targets {
+            self.send_to(&peer_id, frame);
+        }
+    }
+
+    fn drain_inbound(&mut self, _me: &PubKey) -> Vec<Frame> {
+        self.inbound.try_iter().collect()
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wake the accept loop so it notices the flag
+        let _ = TcpStream::connect(self.local_addr);
+        if let Ok(streams) = self.accepted.lock() {
+            for s in streams.values() {
+                let _ = s.shutdown(Shutdown::Both);
+            }
+        }
+        for s in self.conns.values() {
+            let _ = s.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn accept_loop(
+    listener: TcpListener,
+    inbound: SyncSender<Frame>,
+    accepted: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut next_id = 0u64;
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        let id = next_id;
+        next_id += 1;
+        {
+            let mut open = accepted.lock().expect("accepted list poisoned");
+            if open.len() >= MAX_INBOUND_CONNECTIONS {
+                eprintln!("tcp: refusing connection, {} already open", open.len());
+                let _ = stream.shutdown(Shutdown::Both);
+                continue;
+            }
+            if let Ok(clone) = stream.try_clone() {
+                open.insert(id, clone);
+            }
+        }
+        let inbound = inbound.clone();
+        let accepted = accepted.clone();
+        thread::spawn(move || {
+            read_loop(stream, inbound);
+            accepted.lock().expect("accepted list poisoned").remove(&id);
+        });
+    }
+}
+
+fn read_loop(mut stream: TcpStream, inbound: SyncSender<Frame>) {
+    while let Ok(payload) = read_frame(&mut stream) {
+        let frame = match std::str::from_utf8(&payload).ok().and_then(decode_frame) {
+            Some(f) => f,
+            None => {
+                eprintln!("tcp: dropping undecodable frame");
+                continue;
+            }
+        };
+        // blocks while the queue is full; fails once the transport is gone
+        if inbound.send(frame).is_err() {
+            break;
+        }
+    }
+}
+
+fn write_frame(stream: &mut TcpStream, payload: &[u8]) -> io::Result<()> {
+    // the peer would drop the connection on reading it
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame too large"));
+    }
+    let len = payload.len() as u32;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+    // grow the buffer as bytes arrive rather than trusting the claimed
+    // length up front
+    let mut payload = Vec::new();
+    Read::by_ref(stream).take(len as u64).read_to_end(&mut payload)?;
+    if payload.len() < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame"));
+    }
+    Ok(payload)
+}
+
+/// A cached outbound connection is dead once the peer has closed it;
+/// peek without blocking to find out before writing into the void.
+fn is_alive(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let mut probe = [0u8; 1];
+    let alive = match stream.peek(&mut probe) {
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+    };
+    let _ = stream.set_nonblocking(false);
+    alive
+}
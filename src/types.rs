@@ -1,17 +1,30 @@
 use serde::{Serialize, Deserialize};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 use sha2::{Digest as ShaDigest, Sha256};
 
+use crate::canonical;
+use crate::keys::Keypair;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Digest(pub [u8; 32]);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct PubKey(pub String);
+pub struct PubKey(pub [u8; 32]);
+
+impl fmt::Display for PubKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Signature(pub String);
+pub struct Signature(pub Vec<u8>);
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Timestamp(pub u128);
 
 pub fn now_timestamp() -> Timestamp {
@@ -19,18 +32,19 @@ pub fn now_timestamp() -> Timestamp {
     Timestamp(dur.as_millis())
 }
 
+/// SHA-256 over the canonical encoding of `obj` (see `canonical`).
 pub fn compute_digest<T: ?Sized + Serialize>(obj: &T) -> Digest {
-    let json = serde_json::to_string(obj).unwrap();
+    let bytes = canonical::to_bytes(obj).expect("canonical encode");
     let mut h = Sha256::new();
-    h.update(json.as_bytes());
+    h.update(&bytes);
     let out = h.finalize();
     let mut bytes = [0u8; 32];
     bytes.copy_from_slice(&out[..32]);
     Digest(bytes)
 }
 
-pub fn sign_digest(pk: &PubKey, d: &Digest) -> Signature {
-    Signature(format!("SIG{{{}::{:?}}}", pk.0, d.0))
+pub fn sign_digest(key: &Keypair, d: &Digest) -> Signature {
+    key.sign(d)
 }
 
 pub fn zero_digest() -> Digest {
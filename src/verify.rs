@@ -1,18 +1,238 @@
-use crate::content::Message;
-use crate::types::{compute_digest, Digest, Signature, zero_digest};
+use std::fmt;
 
-pub fn verify_digest(msg: &Message) -> bool {
-    let d_local = compute_digest(&msg.content);
+use crate::content::{
+    AttestationBody, Content, Message, RetinaBody, MAX_ATTESTED, certificate_hash, message_digest,
+};
+use crate::keys::verify_signature;
+use crate::message_store::MessageStore;
+use crate::reputation::Offense;
+use crate::retina::{self, PSNR_CAP_DB};
+use crate::types::{Digest, PubKey, zero_digest};
+
+/// Why a message was not accepted into the inbox.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// the digest does not match the header and content
+    BadDigest,
+    /// the digest matches but the sender did not sign it
+    BadSignature,
+    /// the content is well signed but malformed
+    MalformedContent(CertError),
+    /// a well-signed reputation attestation that breaks the format
+    BadAttestation(AttestationError),
+    /// the parent is not in the inbox
+    MissingParent { parent: Digest },
+    /// the sender's reputation is below the admission threshold
+    BelowTrustThreshold { score: f64, threshold: f64 },
+    /// the message was already received
+    Duplicate,
+    /// an orphan refused because too many are already waiting (from
+    /// its sender or in total)
+    TooManyOrphans,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::BadDigest => write!(f, "bad digest"),
+            VerifyError::BadSignature => write!(f, "bad signature"),
+            VerifyError::MalformedContent(e) => write!(f, "malformed content: {}", e),
+            VerifyError::BadAttestation(e) => write!(f, "bad attestation: {}", e),
+            VerifyError::MissingParent { parent } => write!(f, "missing parent {:?}", parent),
+            VerifyError::BelowTrustThreshold { score, threshold } => {
+                write!(f, "sender below trust threshold ({} < {})", score, threshold)
+            }
+            VerifyError::Duplicate => write!(f, "duplicate"),
+            VerifyError::TooManyOrphans => write!(f, "too many orphans waiting"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl VerifyError {
+    /// The offense a sender is punished for, if this failure is
+    /// attributable to them at all.
+    pub fn offense(&self) -> Option<Offense> {
+        match self {
+            VerifyError::MalformedContent(_) | VerifyError::BadAttestation(_) => {
+                Some(Offense::MalformedContent)
+            }
+            VerifyError::MissingParent { .. } => Some(Offense::MissingParent),
+            VerifyError::BelowTrustThreshold { .. } => Some(Offense::BelowThreshold),
+            VerifyError::BadDigest
+            | VerifyError::BadSignature
+            | VerifyError::Duplicate
+            | VerifyError::TooManyOrphans => None,
+        }
+    }
+}
+
+impl From<CertError> for VerifyError {
+    fn from(e: CertError) -> Self {
+        VerifyError::MalformedContent(e)
+    }
+}
+
+impl From<AttestationError> for VerifyError {
+    fn from(e: AttestationError) -> Self {
+        VerifyError::BadAttestation(e)
+    }
+}
+
+pub fn verify_digest(msg: &Message) -> Result<(), VerifyError> {
+    let d_local = message_digest(&msg.sender, &msg.parent, &msg.timestamp, &msg.content);
     if d_local != msg.digest {
-        return false;
+        return Err(VerifyError::BadDigest);
+    }
+    if !verify_signature(&msg.sender, &msg.digest, &msg.signature) {
+        return Err(VerifyError::BadSignature);
+    }
+    Ok(())
+}
+
+pub fn verify_thread(msg: &Message, inbox: &MessageStore) -> Result<(), VerifyError> {
+    if msg.parent == zero_digest() || inbox.contains(&msg.parent) {
+        return Ok(());
+    }
+    Err(VerifyError::MissingParent { parent: msg.parent.clone() })
+}
+
+/// Content-specific checks; only retina bodies carry anything to verify.
+pub fn verify_content(msg: &Message) -> Result<(), VerifyError> {
+    match msg.content {
+        Content::Retina(ref r) => Ok(verify_retina(r)?),
+        Content::Attestation(ref a) => Ok(verify_attestation(&msg.sender, a)?),
+        _ => Ok(()),
     }
-    let expected_sig = Signature(format!("SIG{{{}::{:?}}}", msg.sender.0, msg.digest.0));
-    msg.signature == expected_sig
 }
 
-pub fn verify_thread(msg: &Message, inbox: &[Message]) -> bool {
-    if msg.parent == zero_digest() {
-        return true;
+/// Why a retina certificate was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CertError {
+    BasisFingerprint { expected: String, got: String },
+    CoefficientCount { expected: usize, got: usize },
+    NonFinite,
+    OutOfRange { field: &'static str, value: f64 },
+    HashMismatch,
+}
+
+impl fmt::Display for CertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertError::BasisFingerprint { expected, got } => {
+                write!(f, "basis fingerprint {} != {}", got, expected)
+            }
+            CertError::CoefficientCount { expected, got } => {
+                write!(f, "{} coefficients, basis needs {}", got, expected)
+            }
+            CertError::NonFinite => write!(f, "non-finite value"),
+            CertError::OutOfRange { field, value } => {
+                write!(f, "{} = {} out of range", field, value)
+            }
+            CertError::HashMismatch => write!(f, "certificate hash mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for CertError {}
+
+/// Check that a retina body is well formed and that its certificate hash
+/// matches the body it certifies.
+pub fn verify_retina(body: &RetinaBody) -> Result<(), CertError> {
+    let basis = &body.basis_spec;
+    let expected = retina::basis_fingerprint(basis.nx, basis.ny);
+    if basis.basis_fingerprint != expected {
+        return Err(CertError::BasisFingerprint {
+            expected,
+            got: basis.basis_fingerprint.clone(),
+        });
+    }
+    let expected = basis.nx as usize * basis.ny as usize;
+    if body.a_hat.len() != expected {
+        return Err(CertError::CoefficientCount { expected, got: body.a_hat.len() });
+    }
+
+    let cert = &body.cert;
+    let fov = &body.foveation;
+    let finite = body.a_hat.iter().all(|a| a.is_finite())
+        && [body.lambda, fov.sigma, fov.center_x, fov.center_y]
+            .iter()
+            .all(|v| v.is_finite())
+        && [
+            cert.psnr_equiv_db,
+            cert.fused_variance_drop,
+            cert.foveation_alignment_score,
+        ]
+        .iter()
+        .all(|v| v.is_finite());
+    if !finite {
+        return Err(CertError::NonFinite);
+    }
+
+    let ranges = [
+        ("psnr_equiv_db", cert.psnr_equiv_db, 0.0..=PSNR_CAP_DB),
+        ("fused_variance_drop", cert.fused_variance_drop, f64::MIN_POSITIVE..=1.0),
+        ("foveation_alignment_score", cert.foveation_alignment_score, 0.0..=1.0),
+        ("lambda", body.lambda, 0.0..=f64::MAX),
+    ];
+    if let Some((field, value, _)) = ranges.iter().find(|(_, v, r)| !r.contains(v)) {
+        return Err(CertError::OutOfRange { field, value: *value });
+    }
+
+    if certificate_hash(body) != cert.deterministic_hash {
+        return Err(CertError::HashMismatch);
+    }
+    Ok(())
+}
+
+/// Why a reputation attestation was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttestationError {
+    TooMany { count: usize, max: usize },
+    /// subjects must be strictly increasing (sorted, no repeats)
+    Unsorted { index: usize },
+    ScoreOutOfRange { index: usize, value: f64 },
+    /// the attester scored itself
+    SelfAttestation,
+}
+
+impl fmt::Display for AttestationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttestationError::TooMany { count, max } => {
+                write!(f, "{} subjects, at most {} allowed", count, max)
+            }
+            AttestationError::Unsorted { index } => {
+                write!(f, "subject {} is out of order or repeated", index)
+            }
+            AttestationError::ScoreOutOfRange { index, value } => {
+                write!(f, "subject {}: score {} not in [0, 1]", index, value)
+            }
+            AttestationError::SelfAttestation => write!(f, "attester scores itself"),
+        }
+    }
+}
+
+impl std::error::Error for AttestationError {}
+
+/// Check that an attestation signed by `attester` has at most
+/// `MAX_ATTESTED` distinct subjects in canonical order, each scored in
+/// [0, 1], and does not vouch for the attester itself.
+pub fn verify_attestation(attester: &PubKey, body: &AttestationBody) -> Result<(), AttestationError> {
+    if body.scores.len() > MAX_ATTESTED {
+        return Err(AttestationError::TooMany { count: body.scores.len(), max: MAX_ATTESTED });
+    }
+    for (index, (who, score)) in body.scores.iter().enumerate() {
+        if index > 0 && body.scores[index - 1].0 .0 >= who.0 {
+            return Err(AttestationError::Unsorted { index });
+        }
+        if !(0.0..=1.0).contains(score) {
+            return Err(AttestationError::ScoreOutOfRange { index, value: *score });
+        }
+        if who == attester {
+            return Err(AttestationError::SelfAttestation);
+        }
     }
-    inbox.iter().any(|m| m.digest == msg.parent)
+    Ok(())
 }
@@ -1,11 +1,115 @@
 use serde::{Serialize, Deserialize};
 use crate::content::Message;
+use crate::keys::{verify_signature, Keypair};
+use crate::types::{compute_digest, Digest, PubKey, Signature, Timestamp};
+
+/// How far a signed request's `at` may be from our clock, either way.
+/// Bounds how long a captured request can be replayed.
+pub const REQUEST_MAX_AGE_MS: u128 = 60_000;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WireMessage {
     pub message: Message,
 }
 
+/// Everything that travels between nodes over a Transport.
+/// Messages are the common case, so they are not boxed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Frame {
+    /// A signed canonical message.
+    Message(Message),
+
+    /// Ask peers for `want` plus up to `depth` ancestors of each. The
+    /// answer goes to `from`, so `from` signs the request (see
+    /// `Frame::history_request`).
+    HistoryRequest {
+        from: PubKey,
+        want: Vec<Digest>,
+        depth: u32,
+        at: Timestamp,
+        signature: Signature,
+    },
+
+    /// Answer to a HistoryRequest, ancestors before descendants.
+    HistoryResponse { from: PubKey, messages: Vec<Message> },
+
+    /// Ask a peer for the bytes of a content-addressed object, signed by
+    /// `from` like a HistoryRequest.
+    BlobRequest { from: PubKey, digest: Digest, at: Timestamp, signature: Signature },
+
+    /// Object bytes; the receiver rehashes them before storing.
+    BlobResponse { from: PubKey, digest: Digest, bytes: Vec<u8> },
+}
+
+#[derive(Serialize)]
+struct HistoryRequestPreimage<'a> {
+    domain: &'static str,
+    from: &'a PubKey,
+    want: &'a [Digest],
+    depth: u32,
+    at: &'a Timestamp,
+}
+
+#[derive(Serialize)]
+struct BlobRequestPreimage<'a> {
+    domain: &'static str,
+    from: &'a PubKey,
+    digest: &'a Digest,
+    at: &'a Timestamp,
+}
+
+fn history_request_digest(from: &PubKey, want: &[Digest], depth: u32, at: &Timestamp) -> Digest {
+    compute_digest(&HistoryRequestPreimage {
+        domain: "collapse/history-request/v1",
+        from,
+        want,
+        depth,
+        at,
+    })
+}
+
+fn blob_request_digest(from: &PubKey, digest: &Digest, at: &Timestamp) -> Digest {
+    compute_digest(&BlobRequestPreimage { domain: "collapse/blob-request/v1", from, digest, at })
+}
+
+impl Frame {
+    /// A HistoryRequest from `key`, signed as of `at`.
+    pub fn history_request(key: &Keypair, want: Vec<Digest>, depth: u32, at: Timestamp) -> Frame {
+        let signature = key.sign(&history_request_digest(&key.public, &want, depth, &at));
+        Frame::HistoryRequest { from: key.public.clone(), want, depth, at, signature }
+    }
+
+    /// A BlobRequest from `key`, signed as of `at`.
+    pub fn blob_request(key: &Keypair, digest: Digest, at: Timestamp) -> Frame {
+        let signature = key.sign(&blob_request_digest(&key.public, &digest, &at));
+        Frame::BlobRequest { from: key.public.clone(), digest, at, signature }
+    }
+
+    /// For a request: whether `from` signed it, within
+    /// `REQUEST_MAX_AGE_MS` of `now`. Anyone can put any key in `from`,
+    /// and answering an unsigned one would send our data wherever the
+    /// forger likes. Other frames need no check here and pass.
+    pub fn request_is_signed(&self, now: Timestamp) -> bool {
+        let (from, digest, at, signature) = match self {
+            Frame::HistoryRequest { from, want, depth, at, signature } => {
+                (from, history_request_digest(from, want, *depth, at), at, signature)
+            }
+            Frame::BlobRequest { from, digest, at, signature } => {
+                (from, blob_request_digest(from, digest, at), at, signature)
+            }
+            _ => return true,
+        };
+        now.0.abs_diff(at.0) <= REQUEST_MAX_AGE_MS && verify_signature(from, &digest, signature)
+    }
+}
+
+impl From<Message> for Frame {
+    fn from(msg: Message) -> Self {
+        Frame::Message(msg)
+    }
+}
+
 // Serialize to JSON string for transport.
 pub fn encode_message(msg: &Message) -> String {
     let wm = WireMessage { message: msg.clone() };
@@ -17,3 +121,13 @@ pub fn decode_message(s: &str) -> Option<Message> {
     let parsed: WireMessage = serde_json::from_str(s).ok()?;
     Some(parsed.message)
 }
+
+// Serialize any frame to JSON string for transport.
+pub fn encode_frame(frame: &Frame) -> String {
+    serde_json::to_string(frame).expect("wire encode")
+}
+
+// Deserialize a frame received over transport.
+pub fn decode_frame(s: &str) -> Option<Frame> {
+    serde_json::from_str(s).ok()
+}
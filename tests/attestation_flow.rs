@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use collapse_messenger::content::{AttestationBody, Content, TextBody};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::{assemble_message, Evidence};
+use collapse_messenger::reputation::{ReputationBook, TrustConfig};
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{now_timestamp, zero_digest, Timestamp};
+use collapse_messenger::verify::{verify_attestation, AttestationError, VerifyError};
+use collapse_messenger::wire::Frame;
+
+#[test]
+fn attestation_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let node = || {
+        NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()))
+    };
+    let mut a = node();
+    let mut b = node();
+    let mut c = node();
+    let spammer = Keypair::generate();
+    a.rep = ReputationBook::new().with_trust(TrustConfig::default().with_weight(1.0));
+
+    // 1. A comes to trust B and C first-hand, over enough messages to
+    //    give them tenure as attesters
+    let tenure = a.rep.trust().min_attester_accepted;
+    for n in [&mut b, &mut c] {
+        for i in 0..tenure {
+            n.send(zero_digest(), Evidence::DraftText { raw: format!("post {}", i) }).unwrap();
+        }
+    }
+    a.poll();
+    assert!(a.rep.local(&b.id) >= a.rep.trust().min_attester_score);
+    assert!(a.rep.local(&c.id) >= a.rep.trust().min_attester_score);
+    assert_eq!(a.rep.accepted(&b.id), tenure);
+
+    // 2. B and C were burned by the spammer and say so
+    b.rep.set(&spammer.public, 0.0);
+    c.rep.set(&spammer.public, 0.0);
+    b.publish_attestation();
+    c.publish_attestation();
+    a.poll();
+    assert_eq!(a.rep.attestation(&b.id).unwrap().scores[&spammer.public], 0.0);
+    assert_eq!(a.rep.local(&spammer.public), 0.5);
+    println!("A rep(spammer) = {}", a.rep.get(&spammer.public));
+    assert!(a.rep.get(&spammer.public) < a.rep.admit_threshold());
+
+    // however many agree, they lower a score by at most max_lower
+    let max_lower = a.rep.trust().max_lower;
+    assert!(a.rep.get(&spammer.public) >= a.rep.local(&spammer.public) - max_lower - 1e-9);
+
+    // so A refuses the spammer without ever having been burned by it
+    let spam = assemble_message(
+        &spammer,
+        zero_digest(),
+        Content::Text(TextBody { canonical_text: "buy now".into() }),
+        now_timestamp(),
+    );
+    bus.borrow_mut().send_to(&a.id, &Frame::Message(spam));
+    let receipts = a.poll();
+    let refused = receipts[0].as_ref().unwrap_err();
+    assert!(matches!(refused.error, VerifyError::BelowTrustThreshold { .. }), "{}", refused);
+
+    // 3. vouching raises a score by at most max_raise
+    let max_raise = a.rep.trust().max_raise;
+    assert!(a.rep.get(&c.id) <= a.rep.local(&c.id) + max_raise + 1e-9);
+
+    // 4. identities A has no first-hand trust in cannot vouch, however many
+    let before = a.rep.get(&spammer.public);
+    for _ in 0..5 {
+        let sybil = Keypair::generate().public;
+        assert!(a.rep.attest(&sybil, &[(spammer.public.clone(), 1.0)], now_timestamp()));
+    }
+    assert!((a.rep.get(&spammer.public) - before).abs() < 1e-6);
+
+    // 5. an attestation no newer than the held one is ignored
+    let at = a.rep.attestation(&b.id).unwrap().at;
+    assert!(!a.rep.attest(&b.id, &[(spammer.public.clone(), 1.0)], at));
+    assert_eq!(a.rep.attestation(&b.id).unwrap().scores[&spammer.public], 0.0);
+
+    // nor is one dated far in the future, which would otherwise block
+    // every later attestation from B
+    let far = Timestamp(u128::MAX);
+    assert!(!a.rep.attest(&b.id, &[(spammer.public.clone(), 1.0)], far));
+    assert!(a.rep.attest(&b.id, &[(spammer.public.clone(), 0.1)], now_timestamp()));
+    assert_eq!(a.rep.attestation(&b.id).unwrap().scores[&spammer.public], 0.1);
+
+    // 6. a signed attestation vouching for its own sender is refused and
+    //    punished
+    let m = Keypair::generate();
+    let selfie = assemble_message(
+        &m,
+        zero_digest(),
+        Content::Attestation(AttestationBody { scores: vec![(m.public.clone(), 1.0)] }),
+        now_timestamp(),
+    );
+    bus.borrow_mut().send_to(&a.id, &Frame::Message(selfie));
+    let receipts = a.poll();
+    let refused = receipts[0].as_ref().unwrap_err();
+    assert_eq!(refused.error, VerifyError::BadAttestation(AttestationError::SelfAttestation));
+    assert!(refused.punished);
+    assert!(a.rep.attestation(&m.public).is_none());
+
+    // other malformed bodies
+    let (p, q) = (Keypair::from_seed([1u8; 32]).public, Keypair::from_seed([2u8; 32]).public);
+    let (lo, hi) = if p.0 < q.0 { (p, q) } else { (q, p) };
+    let unsorted = AttestationBody { scores: vec![(hi.clone(), 0.5), (lo.clone(), 0.5)] };
+    assert_eq!(
+        verify_attestation(&m.public, &unsorted),
+        Err(AttestationError::Unsorted { index: 1 })
+    );
+    let out_of_range = AttestationBody { scores: vec![(lo, 0.5), (hi, f64::NAN)] };
+    assert!(matches!(
+        verify_attestation(&m.public, &out_of_range),
+        Err(AttestationError::ScoreOutOfRange { index: 1, .. })
+    ));
+}
+
+#[test]
+fn colluding_fresh_attesters_cannot_sink_a_peer() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut a = NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()));
+    a.rep = ReputationBook::new().with_trust(TrustConfig::default().with_weight(1.0));
+    let honest = Keypair::generate().public;
+    let text = |s: &str| Content::Text(TextBody { canonical_text: s.into() });
+
+    // a ring of fresh identities each posts a couple of valid messages,
+    // enough to clear min_attester_score but not min_attester_accepted
+    let sybils: Vec<Keypair> = (0..10).map(|_| Keypair::generate()).collect();
+    for s in &sybils {
+        for i in 0..2 {
+            let msg = assemble_message(s, zero_digest(), text(&format!("hi {}", i)), now_timestamp());
+            bus.borrow_mut().send_to(&a.id, &Frame::Message(msg));
+        }
+    }
+    a.poll();
+    for s in &sybils {
+        assert!(a.rep.local(&s.public) >= a.rep.trust().min_attester_score);
+        assert!(a.rep.accepted(&s.public) < a.rep.trust().min_attester_accepted);
+    }
+
+    // then all of them attest that an honest peer is worthless
+    for s in &sybils {
+        let smear = assemble_message(
+            s,
+            zero_digest(),
+            Content::Attestation(AttestationBody { scores: vec![(honest.clone(), 0.0)] }),
+            now_timestamp(),
+        );
+        bus.borrow_mut().send_to(&a.id, &Frame::Message(smear));
+    }
+    a.poll();
+    assert!(sybils.iter().all(|s| a.rep.attestation(&s.public).is_some()));
+
+    // without tenure their attestations carry no weight
+    println!("A rep(honest) = {}", a.rep.get(&honest));
+    assert_eq!(a.rep.get(&honest), a.rep.local(&honest));
+    assert!(a.rep.get(&honest) >= a.rep.admit_threshold());
+}
@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use collapse_messenger::content::Content;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::{assemble_message, collapse_evidence, Evidence};
+use collapse_messenger::reputation::{EventReason, Offense, ReputationExport};
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{now_timestamp, zero_digest};
+use collapse_messenger::wire::Frame;
+
+#[test]
+fn audit_flow_demo() {
+    let dir = std::env::temp_dir().join(format!("audit_flow_{}", rand::random::<u64>()));
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    let mut b = NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()));
+    let key_m = Keypair::generate();
+
+    // 1. an accepted message is logged as a reward tied to its digest
+    b.send(zero_digest(), Evidence::DraftText { raw: "hello".into() }).unwrap();
+    let hello = b.inbox[0].digest.clone();
+    a.poll();
+    let history = a.rep.history(&b.id);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].reason, EventReason::Accepted);
+    assert_eq!(history[0].digest, Some(hello));
+    assert!((history[0].delta - 0.1).abs() < 1e-12);
+
+    // 2. a forged certificate is logged as a punishment with the offense
+    let mut forged = match collapse_evidence(
+        Evidence::RawRetinaCapture {
+            samples: vec![(0.5, 0.5, 0.9), (0.6, 0.4, 0.7)],
+            lambda: 1e-3,
+            foveation_cfg: (0.3, 0.5, 0.5),
+            basis_cfg: (2, 2),
+            cert_seed: 1,
+        },
+        &mut MemStore::new(),
+    ).unwrap() {
+        Content::Retina(r) => r,
+        other => panic!("expected retina, got {:?}", other),
+    };
+    forged.a_hat[0] = 42.0;
+    let forged = assemble_message(&key_m, zero_digest(), Content::Retina(forged), now_timestamp());
+    bus.borrow_mut().send_to(&a.id, &Frame::Message(forged.clone()));
+    a.poll();
+    let history = a.rep.history(&key_m.public);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].reason, EventReason::Punished(Offense::MalformedContent));
+    assert_eq!(history[0].digest, Some(forged.digest.clone()));
+    assert!((history[0].delta + 0.2).abs() < 1e-12);
+
+    // 3. scores() and the JSON export agree with the book
+    let scores = a.rep.scores();
+    assert_eq!(scores.len(), 2);
+    assert!((scores[&key_m.public] - a.rep.get(&key_m.public)).abs() < 1e-6);
+    let json = a.rep.export_json();
+    println!("{}", json);
+    let export: ReputationExport = serde_json::from_str(&json).unwrap();
+    assert_eq!(export.scores.len(), 2);
+    assert_eq!(export.events.len(), 2);
+
+    // 4. the log survives a restart
+    drop(a);
+    let a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    assert_eq!(a.rep.events().count(), 2);
+    assert_eq!(a.rep.history(&key_m.public)[0].digest, Some(forged.digest));
+    assert_eq!(a.rep.recorded(), 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
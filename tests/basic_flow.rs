@@ -1,8 +1,9 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
+use collapse_messenger::keys::Keypair;
 use collapse_messenger::node::NodeMessenger;
-use collapse_messenger::types::{PubKey, Digest, zero_digest};
+use collapse_messenger::types::{Digest, zero_digest, Timestamp, now_timestamp};
 use collapse_messenger::phi::Evidence;
 use collapse_messenger::transport_mem::MemoryTransport;
 use collapse_messenger::content::Content;
@@ -13,24 +14,24 @@ fn basic_flow_demo() {
     let bus = Rc::new(RefCell::new(MemoryTransport::new()));
 
     // three nodes registered on the same bus
-    let mut a = NodeMessenger::new(PubKey("A".into()), bus.clone());
-    let mut b = NodeMessenger::new(PubKey("B".into()), bus.clone());
-    let mut c = NodeMessenger::new(PubKey("C".into()), bus.clone());
+    let mut a = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let mut b = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let mut c = NodeMessenger::new(Keypair::generate(), bus.clone());
 
     // note: we record "peers" for social knowledge; delivery is via bus.broadcast()
-    a.add_peer(PubKey("B".into()));
-    a.add_peer(PubKey("C".into()));
-    b.add_peer(PubKey("A".into()));
-    b.add_peer(PubKey("C".into()));
-    c.add_peer(PubKey("A".into()));
-    c.add_peer(PubKey("B".into()));
+    a.add_peer(b.id.clone());
+    a.add_peer(c.id.clone());
+    b.add_peer(a.id.clone());
+    b.add_peer(c.id.clone());
+    c.add_peer(a.id.clone());
+    c.add_peer(b.id.clone());
 
     // 1. A sends a root canonical text message (parent = zero_digest)
     let root_parent = zero_digest();
     a.send(
         root_parent,
         Evidence::DraftText { raw: "hello    world   from A".into() }
-    );
+    ).unwrap();
 
     // At this instant:
     //  - A accepted its own message
@@ -54,7 +55,7 @@ fn basic_flow_demo() {
             basis_cfg: (32, 32),
             cert_seed: 42,
         }
-    );
+    ).unwrap();
 
     // Now bus has B's retinal message queued for A and C
     a.poll();
@@ -65,29 +66,33 @@ fn basic_flow_demo() {
     c.send(
         bogus_parent,
         Evidence::DraftText { raw: "malicious fork attempt".into() }
-    );
+    ).unwrap();
 
     // That bogus message got broadcast too. Let others poll:
     a.poll();
     b.poll();
 
+    // Nobody ever supplies the bogus parent, so the orphan times out.
+    a.expire_pending(Timestamp(now_timestamp().0 + a.pending_timeout_ms + 1));
+
     // After that:
     // - A should have accepted legit messages
     // - A should have punished C for orphan injection
     // - Retinal content from B should be in A's inbox
 
     println!("A inbox len         = {}", a.inbox.len());
-    println!("A rep(A)            = {}", a.rep.get(&PubKey("A".into())));
-    println!("A rep(B)            = {}", a.rep.get(&PubKey("B".into())));
-    println!("A rep(C)            = {}", a.rep.get(&PubKey("C".into())));
+    println!("A rep(A)            = {}", a.rep.get(&a.id));
+    println!("A rep(B)            = {}", a.rep.get(&b.id));
+    println!("A rep(C)            = {}", a.rep.get(&c.id));
 
     assert!(a.inbox.len() >= 2, "A should have at least its own text + B's retinal");
 
     // B should have been rewarded for good behavior
-    assert!(a.rep.get(&PubKey("B".into())) >= 0.6, "B should be rewarded");
+    // (as of the reward: the score fades toward neutral afterwards)
+    assert!(a.rep.saved_scores()[&b.id].score >= 0.6, "B should be rewarded");
 
     // C should have been punished for orphan injection
-    assert!(a.rep.get(&PubKey("C".into())) <= 0.5, "C should be punished/quarantined");
+    assert!(a.rep.get(&c.id) <= 0.5, "C should be punished/quarantined");
 
     // sanity: A saw retinal content
     let a_saw_retina = a.inbox.iter().any(|m|
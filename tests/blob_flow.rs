@@ -0,0 +1,162 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::blob::BlobBody;
+use collapse_messenger::content::Content;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::{assemble_message, Evidence};
+use collapse_messenger::store::{self, Manifest, MemStore};
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{zero_digest, now_timestamp, Timestamp};
+use collapse_messenger::wire::{Frame, REQUEST_MAX_AGE_MS};
+
+#[test]
+fn blob_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut b = NodeMessenger::with_store(
+        Keypair::generate(),
+        Box::new(bus.clone()),
+        Box::new(MemStore::new()),
+    );
+
+    // a "remote" peer on another machine: registered on the bus, but its
+    // bytes are not in our CAS
+    let remote = Keypair::generate();
+    bus.borrow_mut().register_peer(remote.public.clone());
+
+    let bytes: Vec<u8> = (0..4096).map(|_| rand::random::<u8>()).collect();
+    let object_digest = store::object_digest(&bytes);
+    assert!(!b.store.has(&object_digest));
+
+    // 1. remote announces the blob; B accepts the message and asks for bytes
+    let msg = assemble_message(
+        &remote,
+        zero_digest(),
+        Content::Blob(BlobBody {
+            mime: "application/octet-stream".into(),
+            len: bytes.len(),
+            object_digest: object_digest.clone(),
+        }),
+        now_timestamp(),
+    );
+    bus.borrow_mut().send_to(&b.id, &Frame::Message(msg));
+    b.poll();
+    assert_eq!(b.inbox.len(), 1);
+    assert!(b.wanted_blobs.contains(&object_digest));
+
+    let to_remote = bus.borrow_mut().drain_inbound(&remote.public);
+    assert!(matches!(
+        &to_remote[..],
+        [Frame::BlobRequest { digest, .. }] if *digest == object_digest
+    ));
+
+    // 2. tampered bytes are refused; the claimed responder is not
+    //    punished, since anyone can put its key in the frame
+    let rep_before = b.rep.get(&remote.public);
+    let mut evil = bytes.clone();
+    evil[0] ^= 0xff;
+    bus.borrow_mut().send_to(&b.id, &Frame::BlobResponse {
+        from: remote.public.clone(),
+        digest: object_digest.clone(),
+        bytes: evil,
+    });
+    b.poll();
+    assert!(!b.store.has(&object_digest));
+    let rep_after_tamper = b.rep.get(&remote.public);
+
+    // 3. the genuine bytes are verified and stored
+    bus.borrow_mut().send_to(&b.id, &Frame::BlobResponse {
+        from: remote.public.clone(),
+        digest: object_digest.clone(),
+        bytes: bytes.clone(),
+    });
+    b.poll();
+    assert!(b.store.has(&object_digest));
+    assert_eq!(b.store.get(&object_digest).unwrap(), bytes);
+    assert!(b.wanted_blobs.is_empty());
+    println!("B rep(remote) after tampered response = {}", rep_after_tamper);
+    assert!((rep_after_tamper - rep_before).abs() < 1e-9, "forged responses cost nothing");
+
+    // 4. unsolicited objects are not written
+    let junk: Vec<u8> = (0..64).map(|_| rand::random::<u8>()).collect();
+    let junk_digest = store::object_digest(&junk);
+    bus.borrow_mut().send_to(&b.id, &Frame::BlobResponse {
+        from: remote.public.clone(),
+        digest: junk_digest.clone(),
+        bytes: junk,
+    });
+    b.poll();
+    assert!(!b.store.has(&junk_digest));
+
+    // 5. A serves objects it holds to peers that ask
+    let mut a = NodeMessenger::with_store(
+        Keypair::generate(),
+        Box::new(bus.clone()),
+        Box::new(MemStore::new()),
+    );
+    a.send(zero_digest(), Evidence::Blob { bytes: b"served by A".to_vec(), mime: "text/plain".into() }).unwrap();
+    let served = match &a.inbox[0].content {
+        Content::Blob(body) => body.object_digest.clone(),
+        other => panic!("expected blob, got {:?}", other),
+    };
+    bus.borrow_mut().send_to(&a.id, &Frame::blob_request(&remote, served.clone(), now_timestamp()));
+    a.poll();
+    let to_remote = bus.borrow_mut().drain_inbound(&remote.public);
+    let answered = to_remote.iter().any(|f| matches!(
+        f,
+        Frame::BlobResponse { digest, bytes, .. }
+            if *digest == served && Manifest::parse(bytes).is_some()
+    ));
+    assert!(answered, "A should answer the blob request from its CAS");
+
+    // 6. a request naming someone else as `from`, or a stale one, is not
+    //    answered: A would be sending data where the forger points it
+    let forger = Keypair::generate();
+    let forged = match Frame::blob_request(&forger, served.clone(), now_timestamp()) {
+        Frame::BlobRequest { digest, at, signature, .. } => {
+            Frame::BlobRequest { from: remote.public.clone(), digest, at, signature }
+        }
+        other => panic!("expected a blob request, got {:?}", other),
+    };
+    let stale = Timestamp(now_timestamp().0 - REQUEST_MAX_AGE_MS - 1);
+    bus.borrow_mut().send_to(&a.id, &forged);
+    bus.borrow_mut().send_to(&a.id, &Frame::blob_request(&remote, served, stale));
+    a.poll();
+    assert!(bus.borrow_mut().drain_inbound(&remote.public).is_empty());
+}
+
+#[test]
+fn chunked_blob_crosses_between_stores() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let node = || {
+        NodeMessenger::with_store(
+            Keypair::generate(),
+            Box::new(bus.clone()),
+            Box::new(MemStore::new()),
+        )
+    };
+    let mut a = node();
+    let mut b = node();
+
+    // A sends a multi-chunk blob; only A's store has the bytes
+    let bytes: Vec<u8> = (0..700 * 1024).map(|_| rand::random::<u8>()).collect();
+    a.send(zero_digest(), Evidence::Blob { bytes: bytes.clone(), mime: "image/png".into() }).unwrap();
+    let object_digest = match &a.inbox[0].content {
+        Content::Blob(body) => body.object_digest.clone(),
+        other => panic!("expected blob, got {:?}", other),
+    };
+    assert!(!b.store.has(&object_digest));
+
+    // manifest round trip, then chunk round trip
+    for _ in 0..3 {
+        b.poll();
+        a.poll();
+    }
+    b.poll();
+
+    assert!(b.wanted_blobs.is_empty());
+    assert!(store::missing_chunks(b.store.as_ref(), &object_digest).is_empty());
+    assert_eq!(store::get_blob(b.store.as_ref(), &object_digest).unwrap(), bytes);
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use collapse_messenger::canonical::{from_bytes, to_bytes};
+use collapse_messenger::content::{Content, StatusEvent, TextBody, message_digest};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::store::{ChunkRef, Manifest};
+use collapse_messenger::types::{Timestamp, compute_digest, zero_digest};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn enc<T: serde::Serialize + ?Sized>(v: &T) -> String {
+    hex(&to_bytes(v).unwrap())
+}
+
+#[test]
+fn canonical_golden_vectors() {
+    // scalars
+    assert_eq!(enc(&0u8), "0200");
+    assert_eq!(enc(&300u32), "0202012c");
+    assert_eq!(enc(&-1i32), "030101");
+    assert_eq!(enc(&true), "0101");
+    assert_eq!(enc(&()), "00");
+    assert_eq!(enc(&Option::<u8>::None), "00");
+    assert_eq!(enc(&Some(7u8)), "07020107");
+    assert_eq!(enc("hi"), "0501026869");
+
+    // floats: widened to binary64, signed zero and NaN normalized
+    assert_eq!(enc(&1.5f64), "043ff8000000000000");
+    assert_eq!(enc(&1.5f32), enc(&1.5f64));
+    assert_eq!(enc(&-0.0f64), enc(&0.0f64));
+    assert_eq!(enc(&f64::NAN), "047ff8000000000000");
+    assert_eq!(enc(&-f64::NAN), enc(&f64::NAN));
+
+    // sequences and structs
+    assert_eq!(enc(&vec![1u8, 2u8]), "080102020101020102");
+    assert_eq!(
+        enc(&TextBody { canonical_text: "hi".into() }),
+        "0a010105010e63616e6f6e6963616c5f746578740501026869"
+    );
+
+    // enums carry the variant name, not the index
+    assert_eq!(
+        enc(&StatusEvent::TypingStart),
+        format!("0b05010b{}", hex(b"TypingStart"))
+    );
+
+    // map encoding does not depend on insertion / iteration order
+    let mut m1 = HashMap::new();
+    let mut m2 = HashMap::new();
+    for k in 0..32u32 {
+        m1.insert(k, k * 2);
+    }
+    for k in (0..32u32).rev() {
+        m2.insert(k, k * 2);
+    }
+    assert_eq!(to_bytes(&m1).unwrap(), to_bytes(&m2).unwrap());
+
+    // full message digest: pinned so any change to the preimage is caught
+    let key = Keypair::from_seed([1u8; 32]);
+    let content = Content::Text(TextBody { canonical_text: "hello world".into() });
+    let d = message_digest(&key.public, &zero_digest(), &Timestamp(1_700_000_000_000), &content);
+    println!("golden message digest = {}", hex(&d.0));
+    assert_eq!(hex(&d.0), "62bb447741fb205e6d8637b7b8901a3a5028e8659f0b8089c355d3d6ebc2b466");
+
+    // compute_digest is sha256 over the canonical bytes
+    let d = compute_digest(&TextBody { canonical_text: "hi".into() });
+    println!("golden text digest = {}", hex(&d.0));
+    assert_eq!(hex(&d.0), "b55fa536da9b2629d17b36958505c29e6d2798583e0a2c9261c617c8bdad2722");
+}
+
+#[test]
+fn canonical_round_trip() {
+    fn back<T: serde::Serialize + serde::de::DeserializeOwned>(v: &T) -> T {
+        from_bytes(&to_bytes(v).unwrap()).unwrap()
+    }
+    assert_eq!(back(&-5i64), -5);
+    assert_eq!(back(&u128::MAX), u128::MAX);
+    assert_eq!(back(&Some("x".to_string())), Some("x".to_string()));
+    assert_eq!(back(&TextBody { canonical_text: "hi".into() }).canonical_text, "hi");
+    assert!(matches!(back(&StatusEvent::TypingStart), StatusEvent::TypingStart));
+    let content = Content::Text(TextBody { canonical_text: "hello world".into() });
+    assert_eq!(to_bytes(&back(&content)).unwrap(), to_bytes(&content).unwrap());
+
+    // a manifest is stored in canonical form, so its digest is stable
+    let manifest = Manifest {
+        total_len: 3,
+        chunks: vec![ChunkRef { digest: zero_digest(), len: 3 }],
+    };
+    let bytes = manifest.to_bytes();
+    let body = &bytes[bytes.iter().position(|b| *b == b'\n').unwrap() + 1..];
+    assert_eq!(body, to_bytes(&manifest).unwrap().as_slice());
+    assert_eq!(Manifest::parse(&bytes), Some(manifest));
+
+    // only the canonical form decodes
+    assert!(from_bytes::<u8>(&[0x02, 0x01, 0x07, 0x00]).is_err(), "trailing byte");
+    assert!(from_bytes::<u8>(&[0x02, 0x02, 0x00, 0x07]).is_err(), "leading zero");
+    let mut negative_zero = vec![0x04];
+    negative_zero.extend_from_slice(&(-0.0f64).to_bits().to_be_bytes());
+    assert!(from_bytes::<f64>(&negative_zero).is_err(), "negative zero");
+
+    // struct fields must be sorted by encoded name, each exactly once
+    let hi = to_bytes(&TextBody { canonical_text: "hi".into() }).unwrap();
+    let field = &hi[3..]; // after the struct tag and field count
+    let mut repeated = vec![0x0a, 0x01, 0x02];
+    repeated.extend_from_slice(field);
+    repeated.extend_from_slice(field);
+    assert!(from_bytes::<TextBody>(&repeated).is_err(), "repeated field");
+
+    let manifest = to_bytes(&Manifest { total_len: 0, chunks: Vec::new() }).unwrap();
+    let chunks = [0x05, 0x01, 0x06].iter().chain(b"chunks").chain(&[0x08, 0x00]).copied();
+    let total = [0x05, 0x01, 0x09].iter().chain(b"total_len").chain(&[0x02, 0x00]).copied();
+    let mut sorted = vec![0x0a, 0x01, 0x02];
+    sorted.extend(chunks.clone().chain(total.clone()));
+    assert_eq!(sorted, manifest);
+    let mut swapped = vec![0x0a, 0x01, 0x02];
+    swapped.extend(total.chain(chunks));
+    assert!(from_bytes::<Manifest>(&swapped).is_err(), "swapped fields");
+
+    // and so must map keys
+    let entry = |k: u8| [0x02, 0x01, k, 0x02, 0x01, k];
+    let map = |keys: &[u8]| {
+        let mut out = vec![0x09, 0x01, keys.len() as u8];
+        keys.iter().for_each(|k| out.extend_from_slice(&entry(*k)));
+        out
+    };
+    assert!(from_bytes::<HashMap<u8, u8>>(&map(&[1, 2])).is_ok());
+    assert!(from_bytes::<HashMap<u8, u8>>(&map(&[2, 1])).is_err(), "swapped keys");
+    assert!(from_bytes::<HashMap<u8, u8>>(&map(&[1, 1])).is_err(), "repeated key");
+}
@@ -0,0 +1,93 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::content::{Content, RetinaBody, certificate_hash};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::{assemble_message, collapse_evidence, Evidence};
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{now_timestamp, zero_digest};
+use collapse_messenger::verify::{verify_retina, CertError, VerifyError};
+use collapse_messenger::wire::Frame;
+
+fn capture(seed: u64) -> RetinaBody {
+    let mut store = MemStore::new();
+    let content = collapse_evidence(
+        Evidence::RawRetinaCapture {
+            samples: vec![(0.5, 0.5, 0.9), (0.6, 0.4, 0.7), (0.3, 0.7, 0.2)],
+            lambda: 1e-3,
+            foveation_cfg: (0.3, 0.5, 0.5),
+            basis_cfg: (4, 4),
+            cert_seed: seed,
+        },
+        &mut store,
+    ).unwrap();
+    match content {
+        Content::Retina(r) => r,
+        other => panic!("expected retina, got {:?}", other),
+    }
+}
+
+#[test]
+fn certificate_flow_demo() {
+    // 1. a fresh capture carries a verifiable certificate bound to its seed
+    let body = capture(1);
+    println!("certificate = {}", body.cert.deterministic_hash);
+    assert_eq!(body.cert.deterministic_hash, certificate_hash(&body));
+    assert_eq!(verify_retina(&body), Ok(()));
+    assert_ne!(capture(2).cert.deterministic_hash, body.cert.deterministic_hash);
+
+    // 2. each kind of malformed body has its own error
+    let mut bad = body.clone();
+    bad.a_hat[3] += 0.5;
+    assert_eq!(verify_retina(&bad), Err(CertError::HashMismatch));
+
+    let mut bad = body.clone();
+    bad.a_hat.pop();
+    assert_eq!(verify_retina(&bad), Err(CertError::CoefficientCount { expected: 16, got: 15 }));
+
+    let mut bad = body.clone();
+    bad.a_hat[0] = f64::NAN;
+    assert_eq!(verify_retina(&bad), Err(CertError::NonFinite));
+
+    let mut bad = body.clone();
+    bad.cert.psnr_equiv_db = 250.0;
+    bad.cert.deterministic_hash = certificate_hash(&bad);
+    assert!(matches!(
+        verify_retina(&bad),
+        Err(CertError::OutOfRange { field: "psnr_equiv_db", .. })
+    ));
+
+    let mut bad = body.clone();
+    bad.basis_spec.nx = 8;
+    assert!(matches!(verify_retina(&bad), Err(CertError::BasisFingerprint { .. })));
+
+    // 3. a node drops a correctly signed message with a forged certificate
+    //    and punishes its sender, while the honest one is accepted
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let key_a = Keypair::generate();
+    let key_m = Keypair::generate();
+    let mut a = NodeMessenger::with_store(key_a, Box::new(bus.clone()), Box::new(MemStore::new()));
+
+    let mut forged = body.clone();
+    forged.a_hat[0] = 42.0;
+    let honest = assemble_message(&key_m, zero_digest(), Content::Retina(body), now_timestamp());
+    let forged = assemble_message(&key_m, zero_digest(), Content::Retina(forged), now_timestamp());
+    {
+        let mut bus = bus.borrow_mut();
+        bus.send_to(&a.id, &Frame::Message(forged.clone()));
+    }
+    let receipts = a.poll();
+    let rejected = receipts[0].as_ref().unwrap_err();
+    assert_eq!(rejected.error, VerifyError::MalformedContent(CertError::HashMismatch));
+    assert!(rejected.punished);
+    assert!(a.inbox.is_empty(), "forged certificate must not be admitted");
+    assert!(a.rep.get(&key_m.public) < 0.5, "forger should be punished");
+
+    bus.borrow_mut().send_to(&a.id, &Frame::Message(honest.clone()));
+    a.poll();
+    assert_eq!(a.inbox.len(), 1);
+    assert_eq!(a.inbox[0].digest, honest.digest);
+}
@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::content::Content;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::Evidence;
+use collapse_messenger::store::{self, BlobStore, FsStore, Manifest, MemStore, MAX_CHUNK, MIN_CHUNK};
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{Digest, zero_digest};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Seeded so chunk boundaries (and the dedup assertion) are reproducible.
+fn random_bytes(rng: &mut StdRng, n: usize) -> Vec<u8> {
+    (0..n).map(|_| rng.gen::<u8>()).collect()
+}
+
+fn manifest_of(store: &MemStore, digest: &Digest) -> Manifest {
+    Manifest::parse(&store.get(digest).unwrap()).expect("blob should be chunked")
+}
+
+#[test]
+fn chunk_flow_demo() {
+    let mut cas = MemStore::new();
+    let mut rng = StdRng::seed_from_u64(7);
+
+    // 1. a 2 MiB blob is split into bounded chunks and reassembles exactly
+    let original = random_bytes(&mut rng, 2 * 1024 * 1024);
+    let d1 = store::put_stream(&mut cas, &original[..]).unwrap();
+    let m1 = manifest_of(&cas, &d1);
+
+    println!("chunks for 2 MiB = {}", m1.chunks.len());
+    assert!(m1.chunks.len() > 1);
+    assert_eq!(m1.total_len, original.len() as u64);
+    for c in &m1.chunks[..m1.chunks.len() - 1] {
+        assert!(c.len as usize >= MIN_CHUNK && c.len as usize <= MAX_CHUNK);
+    }
+    assert_eq!(store::get_blob(&cas, &d1).unwrap(), original);
+    assert!(store::missing_chunks(&cas, &d1).is_empty());
+
+    // same bytes -> same manifest digest
+    assert_eq!(store::put_blob(&mut cas, &original).unwrap(), d1);
+
+    // 2. insert a few bytes in the middle: boundaries resync, so only the
+    //    chunks around the edit are new
+    let mut edited = original.clone();
+    let insert_at = original.len() / 2;
+    edited.splice(insert_at..insert_at, random_bytes(&mut rng, 100));
+    let d2 = store::put_stream(&mut cas, &edited[..]).unwrap();
+    let m2 = manifest_of(&cas, &d2);
+
+    let old: HashSet<_> = m1.chunks.iter().map(|c| c.digest.clone()).collect();
+    let fresh = m2.chunks.iter().filter(|c| !old.contains(&c.digest)).count();
+    println!("chunks new after edit = {} of {}", fresh, m2.chunks.len());
+    assert!(fresh <= 2, "an insertion should only touch neighbouring chunks");
+    assert_eq!(store::get_blob(&cas, &d2).unwrap(), edited);
+
+    // 3. a node sends a file without loading it into memory
+    let path = std::env::temp_dir().join(format!("chunk_flow_{}.bin", rand::random::<u64>()));
+    let file_bytes = random_bytes(&mut rng, 600 * 1024);
+    std::fs::write(&path, &file_bytes).unwrap();
+
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let root = std::env::temp_dir().join(format!("chunk_flow_cas_{}", rand::random::<u64>()));
+    let mut a = NodeMessenger::with_store(
+        Keypair::generate(),
+        Box::new(bus.clone()),
+        Box::new(FsStore::new(&root)),
+    );
+    a.send(zero_digest(), Evidence::BlobFile { path: path.clone(), mime: "video/mp4".into() }).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let body = match &a.inbox[0].content {
+        Content::Blob(b) => b.clone(),
+        other => panic!("expected blob, got {:?}", other),
+    };
+    assert_eq!(body.len, file_bytes.len());
+    let mut out = Vec::new();
+    let n = store::get_stream(a.store.as_ref(), &body.object_digest, &mut out).unwrap();
+    assert_eq!(n as usize, file_bytes.len());
+    assert_eq!(out, file_bytes);
+
+    // the filesystem store lists manifest + chunks under its own root
+    let listed = a.store.list().unwrap();
+    assert!(listed.contains(&body.object_digest));
+    assert!(listed.len() > 1);
+
+    // 4. an unreadable path is an error, and nothing is sent
+    for bad in [path.clone(), std::env::temp_dir()] {
+        let err = a.send(zero_digest(), Evidence::BlobFile { path: bad, mime: "video/mp4".into() });
+        assert!(err.is_err());
+    }
+    assert_eq!(a.inbox.len(), 1);
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,66 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::Evidence;
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::zero_digest;
+use collapse_messenger::verify::VerifyError;
+use collapse_messenger::wire::Frame;
+
+#[test]
+fn dedup_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let node = || {
+        NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()))
+    };
+    let mut a = node();
+    let mut b = node();
+    let mut c = node();
+
+    // 1. A posts once; B and C accept it and reward A once
+    a.send(zero_digest(), Evidence::DraftText { raw: "only once".into() }).unwrap();
+    let msg = a.inbox[0].clone();
+    b.poll();
+    c.poll();
+    let rep_after_first = b.rep.saved_scores()[&a.id];
+    assert_eq!(b.inbox.len(), 1);
+
+    // 2. C relays A's message to everyone and a replayer hits B directly
+    bus.borrow_mut().broadcast(&c.id, &Frame::Message(msg.clone()));
+    bus.borrow_mut().send_to(&b.id, &Frame::Message(msg.clone()));
+    let receipts = b.poll();
+
+    println!("B receipts = {:?}", receipts);
+    assert_eq!(receipts.len(), 2);
+    for r in &receipts {
+        let r = r.as_ref().unwrap_err();
+        assert_eq!(r.error, VerifyError::Duplicate);
+        assert!(!r.punished);
+    }
+    assert_eq!(b.inbox.len(), 1, "a rebroadcast must not be stored twice");
+    assert_eq!(b.rep.saved_scores()[&a.id], rep_after_first, "a rebroadcast must not earn reputation");
+
+    // 3. A hearing its own message back is just as harmless
+    let receipts = a.poll();
+    assert!(matches!(
+        receipts[0].as_ref().unwrap_err().error,
+        VerifyError::Duplicate
+    ));
+    assert_eq!(a.inbox.len(), 1);
+
+    // 4. a history answer overlapping what B holds adds only the new part
+    c.send(msg.digest.clone(), Evidence::DraftText { raw: "reply".into() }).unwrap();
+    let reply = c.inbox[1].clone();
+    b.poll();
+    bus.borrow_mut().send_to(
+        &b.id,
+        &Frame::HistoryResponse { from: c.id.clone(), messages: vec![msg.clone(), reply.clone()] },
+    );
+    let receipts = b.poll();
+    assert!(receipts.is_empty(), "known history is skipped silently");
+    assert_eq!(b.inbox.len(), 2);
+}
@@ -1,12 +1,14 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
+use collapse_messenger::keys::Keypair;
 use collapse_messenger::node::NodeMessenger;
-use collapse_messenger::types::{PubKey, zero_digest, Digest};
+use collapse_messenger::types::{zero_digest, Digest, Timestamp, now_timestamp};
 use collapse_messenger::phi::Evidence;
 use collapse_messenger::transport_mem::MemoryTransport;
 use collapse_messenger::content::{Content, StatusEvent, RetinaBody};
 use collapse_messenger::fuse::fuse_fixations;
+use collapse_messenger::retina::PSNR_CAP_DB;
 
 #[test]
 fn extended_flow_demo() {
@@ -14,25 +16,25 @@ fn extended_flow_demo() {
     let bus = Rc::new(RefCell::new(MemoryTransport::new()));
 
     // nodes
-    let mut a = NodeMessenger::new(PubKey("A".into()), bus.clone());
-    let mut b = NodeMessenger::new(PubKey("B".into()), bus.clone());
-    let mut c = NodeMessenger::new(PubKey("C".into()), bus.clone());
+    let mut a = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let mut b = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let mut c = NodeMessenger::new(Keypair::generate(), bus.clone());
 
     // "social" peers (not strictly required for bus broadcast,
     // but the node tracks them conceptually)
-    a.add_peer(PubKey("B".into()));
-    a.add_peer(PubKey("C".into()));
-    b.add_peer(PubKey("A".into()));
-    b.add_peer(PubKey("C".into()));
-    c.add_peer(PubKey("A".into()));
-    c.add_peer(PubKey("B".into()));
+    a.add_peer(b.id.clone());
+    a.add_peer(c.id.clone());
+    b.add_peer(a.id.clone());
+    b.add_peer(c.id.clone());
+    c.add_peer(a.id.clone());
+    c.add_peer(b.id.clone());
 
     // 1. A sends root text
     let root_parent = zero_digest();
     a.send(
         root_parent,
         Evidence::DraftText { raw: "THIS_IS_ROOT_MSG_FROM_A".into() }
-    );
+    ).unwrap();
 
     // deliver to B and C
     b.poll();
@@ -53,7 +55,7 @@ fn extended_flow_demo() {
             basis_cfg: (32, 32),
             cert_seed: 7,
         }
-    );
+    ).unwrap();
 
     b.send(
         root_digest.clone(),
@@ -64,7 +66,7 @@ fn extended_flow_demo() {
             basis_cfg: (32, 32),
             cert_seed: 8,
         }
-    );
+    ).unwrap();
 
     // deliver those to A and C
     a.poll();
@@ -83,10 +85,13 @@ fn extended_flow_demo() {
     c.send(
         bogus_parent,
         Evidence::DraftText { raw: "i am chaos".into() }
-    );
+    ).unwrap();
     a.poll();
     b.poll();
 
+    // Nobody ever supplies the bogus parent, so the orphan times out.
+    a.expire_pending(Timestamp(now_timestamp().0 + a.pending_timeout_ms + 1));
+
     // ---- ASSERTIONS ON A'S VIEW ----
     {
         println!("A inbox len (extended) = {}", a.inbox.len());
@@ -108,9 +113,11 @@ fn extended_flow_demo() {
             println!("Retina[{}] lambda = {}", idx, r.lambda);
             println!("Retina[{}] foveation sigma = {}", idx, r.foveation.sigma);
             println!("Retina[{}] a_hat len = {}", idx, r.a_hat.len());
-            assert!(r.a_hat.len() > 0, "a_hat should encode canonical capture state");
-            assert!(r.cert.psnr_equiv_db >= 80.0);
-            assert!(r.cert.foveation_alignment_score >= 1.0);
+            assert!(!r.a_hat.is_empty(), "a_hat should encode canonical capture state");
+            println!("Retina[{}] psnr = {} dB, alignment = {}", idx, r.cert.psnr_equiv_db, r.cert.foveation_alignment_score);
+            assert_eq!(r.a_hat.len(), 32 * 32, "one coefficient per basis function");
+            assert!(r.cert.psnr_equiv_db > 0.0 && r.cert.psnr_equiv_db <= PSNR_CAP_DB);
+            assert!(r.cert.foveation_alignment_score > 0.0 && r.cert.foveation_alignment_score <= 1.0);
         }
 
         // confirm Delivered / Read receipts from B targeting root_digest
@@ -134,8 +141,8 @@ fn extended_flow_demo() {
         assert!(saw_read, "A should have a Read receipt from B");
 
         // check rep movement from A's viewpoint
-        let rep_b = a.rep.get(&PubKey("B".into()));
-        let rep_c = a.rep.get(&PubKey("C".into()));
+        let rep_b = a.rep.get(&b.id);
+        let rep_c = a.rep.get(&c.id);
         println!("A rep(B) after retina+acks = {}", rep_b);
         println!("A rep(C) after orphan      = {}", rep_c);
         assert!(rep_b >= 0.6, "B should be rewarded");
@@ -155,8 +162,8 @@ fn extended_flow_demo() {
 
         let fused_candidate = fuse_fixations(&retina_packets);
         match fused_candidate {
-            None => panic!("expected fused retina"),
-            Some(fused) => {
+            Err(e) => panic!("expected fused retina: {}", e),
+            Ok(fused) => {
                 println!("Fused digest = {:?}", fused.fused_digest);
                 println!("Fused variance_drop = {}", fused.fused.cert.fused_variance_drop);
 
@@ -164,7 +171,7 @@ fn extended_flow_demo() {
                 assert!(fused.fused.cert.fused_variance_drop <= 0.5 + 1e-9);
 
                 // fused a_hat shouldn't be empty in our design
-                assert!(fused.fused.a_hat.len() > 0);
+                assert!(!fused.fused.a_hat.is_empty());
             }
         }
     }
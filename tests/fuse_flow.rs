@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use collapse_messenger::content::{BasisSpec, CertBundle, Content, FoveationSpec, RetinaBody};
+use collapse_messenger::fuse::{fuse_fixations, FuseError};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::Evidence;
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{zero_digest, Digest};
+use collapse_messenger::verify::verify_retina;
+
+fn fixation(a_hat: Vec<f64>, psnr: f64, center_x: f64) -> RetinaBody {
+    RetinaBody {
+        omega_id: "omega/0".to_string(),
+        basis_spec: BasisSpec { nx: 2, ny: 1, basis_fingerprint: "basis/cos2d/2x1".to_string() },
+        a_hat,
+        lambda: 1e-3,
+        foveation: FoveationSpec { sigma: 0.2, center_x, center_y: 0.5 },
+        cert: CertBundle {
+            psnr_equiv_db: psnr,
+            fused_variance_drop: 1.0,
+            foveation_alignment_score: 0.8,
+            seed: 0,
+            samples_digest: zero_digest(),
+            deterministic_hash: "h".to_string(),
+        },
+        fused_from: Vec::new(),
+    }
+}
+
+#[test]
+fn fuse_flow_demo() {
+    // 1. equally good fixations: plain average, variance drops by 1/J
+    let fused = fuse_fixations(&[
+        fixation(vec![1.0, 0.0], 30.0, 0.25),
+        fixation(vec![0.0, 1.0], 30.0, 0.75),
+    ])
+    .unwrap();
+    println!("fused = {:?}", fused.fused);
+    assert_eq!(fused.fused.a_hat, vec![0.5, 0.5]);
+    assert!((fused.fused.cert.fused_variance_drop - 0.5).abs() < 1e-12);
+    assert!((fused.fused.cert.psnr_equiv_db - (30.0 + 10.0 * 2f64.log10())).abs() < 1e-9);
+    assert!((fused.fused.foveation.center_x - 0.5).abs() < 1e-12);
+    assert_eq!(verify_retina(&fused.fused), Ok(()), "fused certificate must verify");
+
+    // 2. a 10 dB better fixation gets 10x the weight, and the drop beats 1/J
+    let fused = fuse_fixations(&[
+        fixation(vec![1.0, 0.0], 40.0, 0.5),
+        fixation(vec![0.0, 1.0], 30.0, 0.5),
+    ])
+    .unwrap();
+    assert!((fused.fused.a_hat[0] - 10.0 / 11.0).abs() < 1e-12);
+    assert!((fused.fused.a_hat[1] - 1.0 / 11.0).abs() < 1e-12);
+    assert!(fused.fused.cert.fused_variance_drop < 0.5);
+
+    // 3. the digest follows the evidence
+    let other = fuse_fixations(&[
+        fixation(vec![1.0, 0.0], 40.0, 0.5),
+        fixation(vec![0.0, 2.0], 30.0, 0.5),
+    ])
+    .unwrap();
+    assert_ne!(fused.fused_digest, other.fused_digest);
+
+    // 4. incompatible inputs are typed errors
+    assert_eq!(fuse_fixations(&[]).unwrap_err(), FuseError::Empty);
+
+    let mut scene = fixation(vec![0.0, 0.0], 30.0, 0.5);
+    scene.omega_id = "omega/1".to_string();
+    let err = fuse_fixations(&[fixation(vec![0.0, 0.0], 30.0, 0.5), scene]).unwrap_err();
+    assert!(matches!(err, FuseError::OmegaMismatch { index: 1, .. }), "{}", err);
+
+    let mut basis = fixation(vec![0.0, 0.0], 30.0, 0.5);
+    basis.basis_spec = BasisSpec { nx: 1, ny: 2, basis_fingerprint: "basis/cos2d/1x2".to_string() };
+    let err = fuse_fixations(&[fixation(vec![0.0, 0.0], 30.0, 0.5), basis]).unwrap_err();
+    assert!(matches!(err, FuseError::BasisMismatch { index: 1, .. }), "{}", err);
+
+    let short = fixation(vec![0.0], 30.0, 0.5);
+    let err = fuse_fixations(&[short]).unwrap_err();
+    assert_eq!(err, FuseError::CoefficientCount { index: 0, expected: 2, got: 1 });
+
+    let nan = fixation(vec![f64::NAN, 0.0], 30.0, 0.5);
+    assert_eq!(fuse_fixations(&[nan]).unwrap_err(), FuseError::NonFinite { index: 0 });
+}
+
+fn capture(value: f32, seed: u64) -> Evidence {
+    Evidence::RawRetinaCapture {
+        samples: vec![(0.5, 0.5, value), (0.4, 0.6, value * 0.5), (0.7, 0.3, 0.1)],
+        lambda: 1e-3,
+        foveation_cfg: (0.3, 0.5, 0.5),
+        basis_cfg: (3, 3),
+        cert_seed: seed,
+    }
+}
+
+#[test]
+fn node_fuses_thread_and_publishes() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let node = || {
+        NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()))
+    };
+    let mut a = node();
+    let mut b = node();
+    a.add_peer(b.id.clone());
+    b.add_peer(a.id.clone());
+
+    // B opens a thread and replies to it with two fixations of one scene
+    b.send(zero_digest(), Evidence::DraftText { raw: "look".into() }).unwrap();
+    let root = b.inbox[0].digest.clone();
+    b.send(root.clone(), capture(0.9, 1)).unwrap();
+    b.send(root.clone(), capture(0.8, 2)).unwrap();
+    a.poll();
+    let sources: Vec<Digest> = a.inbox.children(&root).iter().map(|m| m.digest.clone()).collect();
+    assert_eq!(sources.len(), 2);
+
+    // A fuses them and the result goes out as a reply to the same root
+    let fused_digest = a.fuse_thread(root.clone()).unwrap();
+    let fused_msg = a.inbox.last().unwrap().clone();
+    assert_eq!(fused_msg.digest, fused_digest);
+    assert_eq!(fused_msg.parent, root);
+    let fused = match &fused_msg.content {
+        Content::Retina(r) => r.clone(),
+        other => panic!("expected retina, got {:?}", other),
+    };
+    assert_eq!(fused.fused_from, sources);
+    assert!(fused.cert.fused_variance_drop <= 0.5 + 1e-9);
+
+    // B accepts the fused message: its certificate verifies
+    b.poll();
+    assert_eq!(b.inbox.last().unwrap().digest, fused_digest);
+
+    // fusing again does not feed the first fusion back in
+    a.fuse_thread(root.clone()).unwrap();
+    match &a.inbox.last().unwrap().content {
+        Content::Retina(r) => assert_eq!(r.fused_from, sources),
+        other => panic!("expected retina, got {:?}", other),
+    }
+
+    // sources that are not retina messages we hold are an error
+    let err = a.fuse_digests(root.clone(), std::slice::from_ref(&root)).unwrap_err();
+    assert_eq!(err, FuseError::UnknownSource { digest: root.clone() });
+    assert_eq!(a.fuse_thread(fused_digest.clone()).unwrap_err(), FuseError::Empty);
+
+    // a repeated source counts once: [d, d, d] is just d
+    a.fuse_digests(root.clone(), &[sources[0].clone(), sources[0].clone(), sources[0].clone()]).unwrap();
+    match &a.inbox.last().unwrap().content {
+        Content::Retina(r) => {
+            assert_eq!(r.fused_from, vec![sources[0].clone()]);
+            assert!((r.cert.fused_variance_drop - 1.0).abs() < 1e-9);
+        }
+        other => panic!("expected retina, got {:?}", other),
+    }
+
+    // a fusion and one of the captures already in it overlap
+    let err = a.fuse_digests(root.clone(), &[fused_digest, sources[1].clone()]).unwrap_err();
+    assert_eq!(err, FuseError::Overlap { index: 1, digest: sources[1].clone() });
+}
@@ -0,0 +1,104 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::content::Content;
+use collapse_messenger::gc;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::{collapse_evidence, Evidence};
+use collapse_messenger::store::{self, FsStore, Manifest, MemStore};
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::zero_digest;
+
+fn random_bytes(n: usize) -> Vec<u8> {
+    (0..n).map(|_| rand::random::<u8>()).collect()
+}
+
+#[test]
+fn gc_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut a = NodeMessenger::with_store(
+        Keypair::generate(),
+        Box::new(bus.clone()),
+        Box::new(MemStore::new()),
+    );
+
+    // 1. a blob that is referenced by an accepted message
+    let kept = random_bytes(200 * 1024);
+    a.send(zero_digest(), Evidence::Blob { bytes: kept.clone(), mime: "image/png".into() }).unwrap();
+    let kept_digest = match &a.inbox[0].content {
+        Content::Blob(b) => b.object_digest.clone(),
+        other => panic!("expected blob, got {:?}", other),
+    };
+
+    // 2. a blob collapsed into the store but never sent (think: rejected)
+    let dropped = random_bytes(100 * 1024);
+    let dropped_digest = match collapse_evidence(
+        Evidence::Blob { bytes: dropped, mime: "video/mp4".into() },
+        a.store.as_mut(),
+    ).unwrap() {
+        Content::Blob(b) => b.object_digest,
+        other => panic!("expected blob, got {:?}", other),
+    };
+    let dropped_objects = 1 + Manifest::parse(&a.store.get(&dropped_digest).unwrap())
+        .unwrap()
+        .chunks
+        .len();
+
+    // 3. a blob the user pinned
+    let pinned = random_bytes(1000);
+    let pinned_digest = store::put_blob(a.store.as_mut(), &pinned).unwrap();
+    a.pin_blob(pinned_digest.clone());
+
+    let total_before = a.store.list().unwrap().len();
+
+    // refcounts: the kept manifest and each of its chunks are referenced
+    let live = gc::live_set(a.store.as_ref(), a.inbox.iter(), &a.pins);
+    assert_eq!(live.get(&kept_digest), Some(&1));
+    assert!(!live.contains_key(&dropped_digest));
+
+    // 4. dry run reports but deletes nothing
+    let report = a.gc(true).unwrap();
+    println!(
+        "dry run: live = {}, swept = {}, reclaimable = {} bytes",
+        report.live,
+        report.swept.len(),
+        report.reclaimable_bytes
+    );
+    assert!(report.dry_run);
+    assert_eq!(report.swept.len(), dropped_objects);
+    assert!(report.reclaimable_bytes >= 100 * 1024);
+    assert_eq!(a.store.list().unwrap().len(), total_before);
+
+    // 5. the real sweep removes exactly the unreferenced objects
+    let report = a.gc(false).unwrap();
+    assert_eq!(report.swept.len(), dropped_objects);
+    assert_eq!(a.store.list().unwrap().len(), total_before - dropped_objects);
+    assert!(!a.store.has(&dropped_digest));
+    assert_eq!(store::get_blob(a.store.as_ref(), &kept_digest).unwrap(), kept);
+    assert_eq!(store::get_blob(a.store.as_ref(), &pinned_digest).unwrap(), pinned);
+
+    // 6. unpinning makes the pinned blob collectable
+    a.unpin_blob(&pinned_digest);
+    let report = a.gc(false).unwrap();
+    assert_eq!(report.swept.len(), 2, "manifest + single chunk");
+    assert!(!a.store.has(&pinned_digest));
+    assert_eq!(a.gc(true).unwrap().reclaimable_bytes, 0);
+}
+
+#[test]
+fn default_stores_are_per_node() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut a = NodeMessenger::with_transport(Keypair::generate(), Box::new(bus.clone()));
+    let mut b = NodeMessenger::with_transport(Keypair::generate(), Box::new(bus.clone()));
+
+    // B holds a blob it never sent; A's sweep must not reach it
+    let orphan = store::put_blob(b.store.as_mut(), &random_bytes(1000)).unwrap();
+    a.gc(false).unwrap();
+    assert!(b.store.has(&orphan));
+    assert!(a.store.list().unwrap().is_empty());
+
+    for id in [&a.id, &b.id] {
+        let _ = std::fs::remove_dir_all(FsStore::for_node(id).root());
+    }
+}
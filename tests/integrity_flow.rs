@@ -0,0 +1,79 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::io;
+
+use collapse_messenger::content::Content;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::Evidence;
+use collapse_messenger::store::{self, digest_to_hex, FsStore, Manifest};
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::zero_digest;
+
+#[test]
+fn integrity_flow_demo() {
+    let base = std::env::temp_dir().join(format!("integrity_flow_{}", rand::random::<u64>()));
+    let (root_a, root_b) = (base.join("a"), base.join("b"));
+
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut a = NodeMessenger::with_store(
+        Keypair::generate(),
+        Box::new(bus.clone()),
+        Box::new(FsStore::new(&root_a)),
+    );
+    let mut b = NodeMessenger::with_store(
+        Keypair::generate(),
+        Box::new(bus.clone()),
+        Box::new(FsStore::new(&root_b)),
+    );
+
+    // 1. A sends a chunked blob, B fetches it into its own store
+    let bytes: Vec<u8> = (0..300 * 1024).map(|_| rand::random::<u8>()).collect();
+    a.send(zero_digest(), Evidence::Blob { bytes: bytes.clone(), mime: "image/gif".into() }).unwrap();
+    for _ in 0..3 {
+        b.poll();
+        a.poll();
+    }
+    b.poll();
+    let object_digest = match &b.inbox[0].content {
+        Content::Blob(body) => body.object_digest.clone(),
+        other => panic!("expected blob, got {:?}", other),
+    };
+    assert_eq!(store::get_blob(b.store.as_ref(), &object_digest).unwrap(), bytes);
+
+    // 2. flip a byte inside one of B's chunk files on disk
+    let manifest = Manifest::parse(&b.store.get(&object_digest).unwrap()).unwrap();
+    let victim = manifest.chunks[1].digest.clone();
+    let victim_path = root_b.join(digest_to_hex(&victim));
+    let mut raw = std::fs::read(&victim_path).unwrap();
+    raw[10] ^= 0x01;
+    std::fs::write(&victim_path, &raw).unwrap();
+
+    // reads now fail loudly instead of returning tampered bytes
+    let err = b.store.get(&victim).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(store::get_blob(b.store.as_ref(), &object_digest).is_err());
+
+    // 3. fsck without quarantine only reports
+    let mut scan = FsStore::new(&root_b);
+    let report = store::fsck(&mut scan, false).unwrap();
+    println!("fsck checked {} objects, corrupt = {:?}", report.checked, report.corrupt);
+    assert_eq!(report.corrupt, vec![victim.clone()]);
+    assert!(victim_path.exists());
+
+    // 4. the node repairs: quarantine + re-fetch from peers
+    let report = b.repair_store().unwrap();
+    assert_eq!(report.corrupt, vec![victim.clone()]);
+    assert!(!victim_path.exists());
+    assert!(root_b.join("quarantine").join(digest_to_hex(&victim)).exists());
+    assert!(b.wanted_blobs.contains(&victim));
+
+    a.poll();
+    b.poll();
+
+    assert!(b.wanted_blobs.is_empty());
+    assert_eq!(store::get_blob(b.store.as_ref(), &object_digest).unwrap(), bytes);
+    assert!(store::fsck(&mut scan, false).unwrap().corrupt.is_empty());
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
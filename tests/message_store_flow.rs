@@ -0,0 +1,67 @@
+use collapse_messenger::content::{Content, ContentKind, Message, StatusEvent, TextBody};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::message_store::MessageStore;
+use collapse_messenger::phi::assemble_message;
+use collapse_messenger::types::{Digest, Timestamp, zero_digest};
+
+fn text(key: &Keypair, parent: &Digest, s: &str, at: u128) -> Message {
+    let content = Content::Text(TextBody { canonical_text: s.into() });
+    assemble_message(key, parent.clone(), content, Timestamp(at))
+}
+
+fn digests(ms: &[&Message]) -> Vec<Digest> {
+    ms.iter().map(|m| m.digest.clone()).collect()
+}
+
+#[test]
+fn message_store_flow_demo() {
+    let a = Keypair::from_seed([1u8; 32]);
+    let b = Keypair::from_seed([2u8; 32]);
+
+    //   root ── r1 ── r1a
+    //        └─ r2
+    //   other
+    let root = text(&a, &zero_digest(), "root", 1);
+    let r1 = text(&b, &root.digest, "r1", 2);
+    let r2 = text(&a, &root.digest, "r2", 3);
+    let r1a = text(&a, &r1.digest, "r1a", 4);
+    let other = text(&b, &zero_digest(), "other", 5);
+    let ack = assemble_message(
+        &b,
+        r2.digest.clone(),
+        Content::Status(StatusEvent::TypingStart),
+        Timestamp(6),
+    );
+
+    let mut store = MessageStore::new();
+    for m in [&root, &r1, &r2, &r1a, &other, &ack] {
+        assert!(store.insert(m.clone()));
+    }
+    assert!(!store.insert(r1.clone()), "a digest is stored once");
+    assert_eq!(store.len(), 6);
+
+    // lookup by digest and by position
+    assert_eq!(store.get(&r1a.digest).unwrap().digest, r1a.digest);
+    assert!(store.get(&Digest([7u8; 32])).is_none());
+    assert_eq!(store[2].digest, r2.digest);
+    assert_eq!(store.last().unwrap().digest, ack.digest);
+
+    // thread structure
+    assert_eq!(digests(&store.children(&root.digest)), vec![r1.digest.clone(), r2.digest.clone()]);
+    assert_eq!(digests(&store.children(&zero_digest())), vec![root.digest.clone(), other.digest.clone()]);
+    assert_eq!(digests(&store.ancestors(&r1a.digest)), vec![r1.digest.clone(), root.digest.clone()]);
+    assert!(store.ancestors(&root.digest).is_empty());
+    assert_eq!(
+        digests(&store.thread(&root.digest)),
+        vec![root.digest.clone(), r1.digest.clone(), r2.digest.clone(), r1a.digest.clone(), ack.digest.clone()]
+    );
+    assert_eq!(digests(&store.thread(&other.digest)), vec![other.digest.clone()]);
+
+    // secondary indexes
+    assert_eq!(
+        digests(&store.by_sender(&b.public)),
+        vec![r1.digest.clone(), other.digest.clone(), ack.digest.clone()]
+    );
+    assert_eq!(digests(&store.by_kind(ContentKind::Status)), vec![ack.digest.clone()]);
+    assert!(store.by_kind(ContentKind::Blob).is_empty());
+}
@@ -0,0 +1,132 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::types::{Digest, Timestamp, zero_digest, now_timestamp};
+use collapse_messenger::content::{Content, TextBody};
+use collapse_messenger::phi::assemble_message;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::verify::VerifyError;
+use collapse_messenger::wire::Frame;
+
+fn text(s: &str) -> Content {
+    Content::Text(TextBody { canonical_text: s.into() })
+}
+
+#[test]
+fn pending_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+
+    let key_a = Keypair::generate();
+    let key_b = Keypair::generate();
+    let key_c = Keypair::generate();
+    let mut c = NodeMessenger::new(key_c, bus.clone());
+
+    // A posts a root, B replies, C replies to B: a 3-deep thread
+    let root = assemble_message(&key_a, zero_digest(), text("root"), now_timestamp());
+    let reply = assemble_message(&key_b, root.digest.clone(), text("reply"), now_timestamp());
+    let reply2 = assemble_message(&key_a, reply.digest.clone(), text("reply to reply"), now_timestamp());
+
+    // 1. the network delivers them to C newest-first
+    {
+        let mut bus = bus.borrow_mut();
+        bus.send_to(&c.id, &Frame::Message(reply2.clone()));
+        bus.send_to(&c.id, &Frame::Message(reply.clone()));
+    }
+    c.poll();
+
+    // orphans are held, nobody is punished for reordering
+    assert_eq!(c.inbox.len(), 0);
+    assert_eq!(c.pending_len(), 2);
+    assert_eq!(c.rep.get(&key_a.public), 0.5);
+    assert_eq!(c.rep.get(&key_b.public), 0.5);
+
+    // 2. the root arrives; the whole chain is released in causal order
+    bus.borrow_mut().send_to(&c.id, &Frame::Message(root.clone()));
+    let receipts = c.poll();
+    let accepted = receipts[0].as_ref().expect("root accepted");
+    assert_eq!(accepted.released, vec![reply.digest.clone(), reply2.digest.clone()]);
+
+    println!("C inbox len = {}", c.inbox.len());
+    assert_eq!(c.pending_len(), 0);
+    let order: Vec<Digest> = c.inbox.iter().map(|m| m.digest.clone()).collect();
+    assert_eq!(order, vec![root.digest.clone(), reply.digest.clone(), reply2.digest.clone()]);
+    assert!(c.rep.get(&key_b.public) > 0.5, "B should be rewarded once released");
+
+    // 3. an orphan whose parent never arrives is only punished after expiry
+    let orphan = assemble_message(&key_b, Digest([9u8; 32]), text("dangling"), now_timestamp());
+    bus.borrow_mut().send_to(&c.id, &Frame::Message(orphan.clone()));
+    let receipts = c.poll();
+    let held = receipts[0].as_ref().unwrap_err();
+    assert_eq!(held.error, VerifyError::MissingParent { parent: Digest([9u8; 32]) });
+    assert!(!held.punished);
+    let rep_b_before = c.rep.get(&key_b.public);
+    assert_eq!(c.pending_len(), 1);
+
+    let later = Timestamp(now_timestamp().0 + c.pending_timeout_ms + 1);
+    let expired = c.expire_pending(later);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].digest, orphan.digest);
+    assert!(expired[0].punished);
+
+    let rep_b_after = c.rep.get(&key_b.public);
+    println!("C rep(B) before expiry = {}", rep_b_before);
+    println!("C rep(B) after expiry  = {}", rep_b_after);
+    assert_eq!(c.pending_len(), 0);
+    assert!(rep_b_after < rep_b_before, "B should be punished once the orphan expires");
+    assert!(c.inbox.iter().all(|m| m.digest != orphan.digest));
+}
+
+#[test]
+fn orphan_buffer_is_bounded() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut c = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let watcher = Keypair::generate().public;
+    bus.borrow_mut().register_peer(watcher.clone());
+
+    // one sender floods C with orphans for parents nobody has
+    let key_s = Keypair::generate();
+    let flood: Vec<_> = (0..c.pending_per_sender + 6)
+        .map(|i| {
+            let mut parent = [0xffu8; 32];
+            parent[..8].copy_from_slice(&(i as u64).to_be_bytes());
+            let parent = Digest(parent);
+            assemble_message(&key_s, parent, text("orphan"), now_timestamp())
+        })
+        .collect();
+    for msg in &flood {
+        bus.borrow_mut().send_to(&c.id, &Frame::Message(msg.clone()));
+    }
+    let receipts = c.poll();
+
+    // only its share is buffered; the rest is refused, unpunished
+    assert_eq!(c.pending_len(), c.pending_per_sender);
+    let refused: Vec<_> = receipts
+        .iter()
+        .filter_map(|r| r.as_ref().err())
+        .filter(|r| r.error == VerifyError::TooManyOrphans)
+        .collect();
+    assert_eq!(refused.len(), 6);
+    assert!(refused.iter().all(|r| !r.punished));
+    assert_eq!(c.rep.get(&key_s.public), 0.5);
+
+    // and the missing parents cost one history request, not one each
+    let requests = bus.borrow_mut().drain_inbound(&watcher);
+    assert_eq!(requests.len(), 1, "history requests are rate-limited");
+
+    // other senders still get buffer space
+    let other = assemble_message(&Keypair::generate(), Digest([0xee; 32]), text("hi"), now_timestamp());
+    bus.borrow_mut().send_to(&c.id, &Frame::Message(other));
+    c.poll();
+    assert_eq!(c.pending_len(), c.pending_per_sender + 1);
+
+    // heal asks for everything still missing at once
+    c.heal();
+    let requests = bus.borrow_mut().drain_inbound(&watcher);
+    match requests.as_slice() {
+        [Frame::HistoryRequest { want, .. }] => assert_eq!(want.len(), c.pending_len()),
+        other => panic!("expected one history request, got {:?}", other),
+    }
+}
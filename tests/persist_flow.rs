@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::rc::Rc;
+
+use collapse_messenger::content::Content;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::Evidence;
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{zero_digest, Digest};
+use collapse_messenger::verify::{verify_content, verify_digest};
+
+fn digests(n: &NodeMessenger) -> Vec<Digest> {
+    n.inbox.iter().map(|m| m.digest.clone()).collect()
+}
+
+/// Reloaded messages must still verify, or the node would serve history
+/// its peers reject.
+fn assert_intact(n: &NodeMessenger) {
+    for m in n.inbox.iter() {
+        assert_eq!(verify_digest(m), Ok(()), "{:?}", m.content.kind());
+        assert_eq!(verify_content(m), Ok(()), "{:?}", m.content.kind());
+    }
+}
+
+#[test]
+fn persist_flow_demo() {
+    let dir = std::env::temp_dir().join(format!("persist_flow_{}", rand::random::<u64>()));
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut b = NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()));
+
+    // 1. A lives in `dir` and accepts a small thread from B
+    let mut a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    let a_id = a.id.clone();
+    b.send(zero_digest(), Evidence::DraftText { raw: "hello A".into() }).unwrap();
+    let root = b.inbox[0].digest.clone();
+    b.send(
+        root.clone(),
+        Evidence::RawRetinaCapture {
+            samples: vec![(0.5, 0.5, 0.9), (0.4, 0.6, 0.3)],
+            lambda: 1e-3,
+            foveation_cfg: (0.3, 0.5, 0.5),
+            basis_cfg: (2, 2),
+            cert_seed: 3,
+        },
+    ).unwrap();
+    a.poll();
+    a.send(root.clone(), Evidence::DraftText { raw: "hi B".into() }).unwrap();
+    b.poll();
+    b.publish_attestation();
+    a.poll();
+    let before = digests(&a);
+    let rep_b = a.rep.saved_scores()[&b.id];
+    assert_eq!(before.len(), 4);
+    assert!(rep_b.score > 0.5);
+    drop(a);
+
+    // 2. a restart brings back identity, inbox, reputation and retina cache
+    let a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    println!("restored {} messages, rep(B) = {}", a.inbox.len(), a.rep.get(&b.id));
+    assert_eq!(a.id, a_id);
+    assert_eq!(digests(&a), before);
+    assert_intact(&a);
+    assert_eq!(a.rep.saved_scores()[&b.id], rep_b);
+    assert_eq!(a.retina_store.len(), 1);
+    assert_eq!(a.inbox.children(&root).len(), 2);
+    drop(a);
+
+    // 3. a torn write at the end of the log is dropped, not fatal, and
+    //    later appends land on a clean frame boundary
+    {
+        let mut log = OpenOptions::new().append(true).open(dir.join("log")).unwrap();
+        log.write_all(&[0, 0, 0, 200, 1, 2, 3, 4, b'{', b'"']).unwrap();
+    }
+    let mut a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    assert_eq!(digests(&a), before);
+    a.send(root.clone(), Evidence::DraftText { raw: "after the crash".into() }).unwrap();
+    let after = digests(&a);
+    drop(a);
+    let a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    assert_eq!(digests(&a), after);
+    drop(a);
+
+    // 4. a snapshot empties the log and restores the same state
+    let mut a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    a.save().unwrap();
+    assert_eq!(fs::metadata(dir.join("log")).unwrap().len(), 0);
+    drop(a);
+    let a = NodeMessenger::open(&dir, Box::new(bus.clone())).unwrap();
+    assert_eq!(digests(&a), after);
+    assert_intact(&a);
+    assert_eq!(a.rep.saved_scores()[&b.id], rep_b);
+    assert!(matches!(a.inbox[1].content, Content::Retina(_)));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use collapse_messenger::content::{Content, Message, TextBody};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::{NodeMessenger, Review};
+use collapse_messenger::phi::assemble_message;
+use collapse_messenger::reputation::EventReason;
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{now_timestamp, zero_digest, Digest, Timestamp};
+use collapse_messenger::verify::VerifyError;
+use collapse_messenger::wire::Frame;
+
+fn text(key: &Keypair, parent: Digest, body: &str) -> Message {
+    assemble_message(
+        key,
+        parent,
+        Content::Text(TextBody { canonical_text: body.into() }),
+        now_timestamp(),
+    )
+}
+
+#[test]
+fn quarantine_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let mut a = NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()));
+    let key_m = Keypair::generate();
+    let m = key_m.public.clone();
+    a.rep.set(&m, 0.1);
+    let a_id = a.id.clone();
+    let deliver = |msg: &Message| bus.borrow_mut().send_to(&a_id, &Frame::Message(msg.clone()));
+
+    // 1. a low-scored sender's message is quarantined, not punished again
+    let root = text(&key_m, zero_digest(), "sorry about earlier");
+    deliver(&root);
+    let receipts = a.poll();
+    let held = receipts[0].as_ref().unwrap_err();
+    assert!(matches!(held.error, VerifyError::BelowTrustThreshold { .. }), "{}", held);
+    assert!(!held.punished);
+    assert!(a.is_quarantined(&root.digest));
+    assert!(a.inbox.is_empty());
+    assert!(a.rep.history(&m).is_empty());
+
+    // resending it changes nothing; a reply to it waits as an orphan
+    deliver(&root);
+    let reply = text(&key_m, root.digest.clone(), "follow-up");
+    deliver(&reply);
+    let receipts = a.poll();
+    assert_eq!(receipts[0].as_ref().unwrap_err().error, VerifyError::Duplicate);
+    assert!(matches!(
+        receipts[1].as_ref().unwrap_err().error,
+        VerifyError::MissingParent { .. }
+    ));
+    assert_eq!(a.quarantine.len(), 1);
+
+    // the reply outlives the orphan timeout: its parent is here, only held
+    let later = Timestamp(now_timestamp().0 + a.pending_timeout_ms + 1);
+    assert!(a.expire_pending(later).is_empty());
+    assert_eq!(a.pending_len(), 1);
+    assert!(a.rep.history(&m).is_empty());
+
+    // 2. releasing with restore accepts it and rewards the sender; the
+    //    orphan is checked again and, its sender still low, quarantined
+    let accepted = a.release(&root.digest, true).unwrap().unwrap();
+    println!("released = {:?}", accepted);
+    assert!(a.inbox.contains(&root.digest));
+    assert!((a.rep.local(&m) - 0.2).abs() < 1e-6);
+    assert_eq!(a.rep.history(&m)[0].reason, EventReason::Accepted);
+    assert_eq!(accepted.refused.len(), 1);
+    assert!(a.is_quarantined(&reply.digest));
+
+    // a plain release accepts without touching the score
+    a.release(&reply.digest, false).unwrap().unwrap();
+    assert!(a.inbox.contains(&reply.digest));
+    assert_eq!(a.rep.history(&m).len(), 1);
+    assert!(a.release(&reply.digest, false).is_none(), "no longer quarantined");
+
+    // 3. discarding drops the message and punishes the sender
+    let junk = text(&key_m, zero_digest(), "junk");
+    deliver(&junk);
+    a.poll();
+    let discarded = a.discard(&junk.digest).unwrap();
+    assert!(discarded.punished);
+    assert!(!a.is_quarantined(&junk.digest));
+    assert!(a.rep.local(&m) < 0.2);
+
+    // 4. a review policy: release what senders back above the threshold
+    //    sent while they were below it, keep the rest
+    let key_n = Keypair::generate();
+    a.rep.set(&key_n.public, 0.0);
+    let from_m = text(&key_m, zero_digest(), "m again");
+    let from_n = text(&key_n, zero_digest(), "n");
+    deliver(&from_m);
+    deliver(&from_n);
+    a.poll();
+    assert_eq!(a.quarantine.len(), 2);
+    a.rep.set(&m, 0.5);
+    let receipts = a.review_quarantine(|q, rep| {
+        if rep.get(&q.msg.sender) >= rep.admit_threshold() {
+            Review::Release { restore: false }
+        } else {
+            Review::Keep
+        }
+    });
+    assert_eq!(receipts.len(), 1);
+    assert!(a.inbox.contains(&from_m.digest));
+    assert!(a.is_quarantined(&from_n.digest));
+
+    // 5. the quarantine is bounded: the oldest message goes first
+    a.quarantine_cap = 2;
+    let later: Vec<Message> = (0..2).map(|i| text(&key_n, zero_digest(), &format!("n{}", i))).collect();
+    for msg in &later {
+        deliver(msg);
+    }
+    a.poll();
+    assert_eq!(a.quarantine.len(), 2);
+    assert!(!a.is_quarantined(&from_n.digest));
+    assert!(later.iter().all(|msg| a.is_quarantined(&msg.digest)));
+}
@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::phi::{assemble_message, Evidence};
+use collapse_messenger::content::{Content, TextBody};
+use collapse_messenger::reputation::{
+    ConfigError, Offense, PeerScore, ReputationBook, ReputationConfig, ReputationPolicy, StepPolicy,
+};
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::types::{now_timestamp, zero_digest, Digest, PubKey, Timestamp};
+use collapse_messenger::wire::Frame;
+
+/// Beta-distribution reputation: score = (successes + 1) / (trials + 2),
+/// with each offense counting as `weight` failures.
+#[derive(Default)]
+struct BetaPolicy {
+    counts: HashMap<PubKey, (f64, f64)>,
+}
+
+impl ReputationPolicy for BetaPolicy {
+    fn score(&self, who: &PubKey, _now: Timestamp) -> f64 {
+        let (good, bad) = self.counts.get(who).copied().unwrap_or((0.0, 0.0));
+        (good + 1.0) / (good + bad + 2.0)
+    }
+
+    fn reward(&mut self, who: &PubKey, _now: Timestamp) {
+        self.counts.entry(who.clone()).or_default().0 += 1.0;
+    }
+
+    fn punish(&mut self, who: &PubKey, offense: Offense, _now: Timestamp) {
+        let weight = if offense == Offense::MissingParent { 0.5 } else { 2.0 };
+        self.counts.entry(who.clone()).or_default().1 += weight;
+    }
+
+    fn admit_threshold(&self) -> f64 {
+        0.3
+    }
+
+    // counts never fade, so the time of the last change does not matter
+    fn scores(&self) -> HashMap<PubKey, PeerScore> {
+        let at = Timestamp(0);
+        self.counts.keys().map(|p| (p.clone(), PeerScore { score: self.score(p, at), at })).collect()
+    }
+
+    fn set(&mut self, who: &PubKey, score: PeerScore) {
+        let score = score.score;
+        self.counts.insert(who.clone(), (score * 10.0, (1.0 - score) * 10.0));
+    }
+}
+
+#[test]
+fn reputation_flow_demo() {
+    let peer = Keypair::from_seed([4u8; 32]).public;
+
+    // 1. the default config keeps the historical steps
+    let mut book = ReputationBook::new();
+    assert_eq!(book.get(&peer), 0.5);
+    book.reward(&peer, None);
+    assert!((book.get(&peer) - 0.6).abs() < 1e-6);
+    book.punish(&peer, Offense::MissingParent, None);
+    assert!((book.get(&peer) - 0.4).abs() < 1e-6);
+    assert_eq!(book.admit_threshold(), 0.30);
+
+    // 2. a JSON config overrides only what it names, with per-offense weights
+    let cfg = ReputationConfig::from_json(
+        r#"{ "punish_step": 0.3, "admit_threshold": 0.2, "weights": { "MissingParent": 0.5, "BelowThreshold": 2.0 } }"#,
+    )
+    .unwrap();
+    assert_eq!(cfg.reward_step, 0.1);
+    let mut book = ReputationBook::with_config(cfg).unwrap();
+    book.punish(&peer, Offense::MissingParent, None);
+    assert!((book.get(&peer) - 0.35).abs() < 1e-6);
+    book.punish(&peer, Offense::BelowThreshold, None);
+    assert_eq!(book.saved_scores()[&peer].score, 0.0, "clamped at the floor");
+    assert_eq!(book.admit_threshold(), 0.2);
+
+    // the builder produces the same kind of config
+    let built = ReputationConfig::default()
+        .with_punish_step(0.3)
+        .with_admit_threshold(0.2)
+        .with_weight(Offense::MissingParent, 0.5)
+        .with_weight(Offense::BelowThreshold, 2.0);
+    assert_eq!(built.weight(Offense::MissingParent), 0.5);
+    assert_eq!(built.weight(Offense::MalformedContent), 1.0);
+
+    // configs that would break clamping or scoring are refused
+    let inverted = ReputationConfig::default().with_bounds(1.0, 0.5, 0.0);
+    assert!(matches!(ReputationBook::with_config(inverted), Err(ConfigError::Bounds { .. })));
+    let nan = ReputationConfig::default().with_reward_step(f64::NAN);
+    assert!(matches!(StepPolicy::new(nan), Err(ConfigError::NotFinite { field: "reward_step" })));
+    let negative = ReputationConfig::default().with_weight(Offense::BelowThreshold, -1.0);
+    assert!(matches!(negative.validate(), Err(ConfigError::BadWeight { offense: Offense::BelowThreshold, .. })));
+    assert!(matches!(
+        ReputationConfig::from_json(r#"{ "floor": 0.6 }"#),
+        Err(ConfigError::Bounds { .. })
+    ));
+
+    // 3. scores fade toward neutral from both sides, one half per
+    //    half-life, without any call in between
+    let hour = 60 * 60 * 1000;
+    let mut book = ReputationBook::with_config(ReputationConfig::default().with_half_life_ms(hour)).unwrap();
+    let other = Keypair::from_seed([5u8; 32]).public;
+    book.restore(&peer, PeerScore { score: 0.1, at: Timestamp(0) });
+    book.restore(&other, PeerScore { score: 0.9, at: Timestamp(0) });
+    assert!((book.get_at(&peer, Timestamp(hour as u128)) - 0.3).abs() < 1e-12);
+    assert!((book.get_at(&other, Timestamp(hour as u128)) - 0.7).abs() < 1e-12);
+    assert!((book.get_at(&other, Timestamp(2 * hour as u128)) - 0.6).abs() < 1e-12);
+    assert!((book.get(&peer) - 0.5).abs() < 1e-9, "long idle peers are back at neutral");
+
+    // a step applies to the faded score, and fading restarts from it
+    let mut policy = StepPolicy::new(ReputationConfig::default().with_half_life_ms(hour)).unwrap();
+    policy.set(&peer, PeerScore { score: 0.1, at: Timestamp(0) });
+    policy.reward(&peer, Timestamp(hour as u128));
+    assert!((policy.score(&peer, Timestamp(hour as u128)) - 0.4).abs() < 1e-12);
+    assert!((policy.score(&peer, Timestamp(2 * hour as u128)) - 0.45).abs() < 1e-12);
+
+    // a zero half-life keeps scores where they are
+    let policy = {
+        let mut p = StepPolicy::new(ReputationConfig::default().with_half_life_ms(0)).unwrap();
+        p.set(&peer, PeerScore { score: 0.1, at: Timestamp(0) });
+        p
+    };
+    assert_eq!(policy.score(&peer, Timestamp(100 * hour as u128)), 0.1);
+}
+
+#[test]
+fn node_runs_a_plugged_in_policy() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+    let node = || {
+        NodeMessenger::with_store(Keypair::generate(), Box::new(bus.clone()), Box::new(MemStore::new()))
+    };
+    let mut a = node();
+    let mut b = node();
+    a.rep = ReputationBook::with_policy(BetaPolicy::default());
+
+    // two accepted messages: Beta(3, 1) -> 0.75
+    b.send(zero_digest(), Evidence::DraftText { raw: "one".into() }).unwrap();
+    b.send(zero_digest(), Evidence::DraftText { raw: "two".into() }).unwrap();
+    a.poll();
+    assert!((a.rep.get(&b.id) - 0.75).abs() < 1e-12);
+
+    // an expired orphan costs half a failure under this policy
+    let key_m = Keypair::generate();
+    let orphan = assemble_message(
+        &key_m,
+        Digest([8u8; 32]),
+        Content::Text(TextBody { canonical_text: "dangling".into() }),
+        now_timestamp(),
+    );
+    bus.borrow_mut().send_to(&a.id, &Frame::Message(orphan));
+    a.poll();
+    a.expire_pending(Timestamp(now_timestamp().0 + a.pending_timeout_ms + 1));
+    assert!((a.rep.get(&key_m.public) - 1.0 / 2.5).abs() < 1e-12);
+}
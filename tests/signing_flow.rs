@@ -0,0 +1,115 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use collapse_messenger::keys::{Keypair, verify_signature};
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::types::{Digest, Timestamp, zero_digest, now_timestamp};
+use collapse_messenger::content::{Content, TextBody, message_digest};
+use collapse_messenger::phi::assemble_message;
+use collapse_messenger::transport::Transport;
+use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::wire::Frame;
+use collapse_messenger::verify::{verify_digest, VerifyError};
+
+#[test]
+fn signing_flow_demo() {
+    let bus = Rc::new(RefCell::new(MemoryTransport::new()));
+
+    let key_a = Keypair::from_seed([1u8; 32]);
+    let key_m = Keypair::from_seed([2u8; 32]);
+
+    let a = NodeMessenger::new(key_a.clone(), bus.clone());
+    let mut b = NodeMessenger::new(Keypair::generate(), bus.clone());
+
+    // 1. an honest message verifies, and the signature is bound to the key
+    let honest = assemble_message(
+        &key_a,
+        zero_digest(),
+        Content::Text(TextBody { canonical_text: "hi from A".into() }),
+        now_timestamp(),
+    );
+    assert_eq!(verify_digest(&honest), Ok(()));
+    assert!(verify_signature(&key_a.public, &honest.digest, &honest.signature));
+    assert!(!verify_signature(&key_m.public, &honest.digest, &honest.signature));
+
+    // 2. mallory signs with its own key but claims to be A
+    let mut forged = assemble_message(
+        &key_m,
+        zero_digest(),
+        Content::Text(TextBody { canonical_text: "send money to mallory".into() }),
+        now_timestamp(),
+    );
+    forged.sender = a.id.clone();
+    assert_eq!(verify_digest(&forged), Err(VerifyError::BadDigest), "forged sender must not verify");
+
+    // 2b. ... and even with the digest recomputed, the signature is not A's
+    let mut resigned = forged.clone();
+    resigned.digest = message_digest(&resigned.sender, &resigned.parent, &resigned.timestamp, &resigned.content);
+    assert_eq!(verify_digest(&resigned), Err(VerifyError::BadSignature));
+
+    // 3. a relayer tampers with A's content but keeps A's signature
+    let mut tampered = honest.clone();
+    tampered.content = Content::Text(TextBody { canonical_text: "hi from Mallory".into() });
+    assert_eq!(verify_digest(&tampered), Err(VerifyError::BadDigest), "tampered content must not verify");
+
+    // 4. deliver all three to B over the bus
+    {
+        let mut bus = bus.borrow_mut();
+        bus.send_to(&b.id, &Frame::Message(forged.clone()));
+        bus.send_to(&b.id, &Frame::Message(resigned.clone()));
+        bus.send_to(&b.id, &Frame::Message(tampered.clone()));
+        bus.send_to(&b.id, &Frame::Message(honest.clone()));
+    }
+    let receipts = b.poll();
+
+    // the poll report says why each one was refused
+    let errors: Vec<VerifyError> = receipts
+        .iter()
+        .filter_map(|r| r.as_ref().err())
+        .map(|r| r.error.clone())
+        .collect();
+    assert_eq!(
+        errors,
+        vec![VerifyError::BadDigest, VerifyError::BadSignature, VerifyError::BadDigest]
+    );
+    assert!(receipts.iter().filter_map(|r| r.as_ref().err()).all(|r| !r.punished));
+    assert_eq!(receipts[3].as_ref().unwrap().digest, honest.digest);
+
+    println!("B inbox len = {}", b.inbox.len());
+    println!("B rep(A)    = {}", b.rep.get(&a.id));
+    assert_eq!(b.inbox.len(), 1, "only the honest message is accepted");
+    assert_eq!(b.inbox[0].digest, honest.digest);
+
+    // forgeries are unattributable, so A is not punished for them
+    assert!(b.rep.get(&a.id) > 0.5, "A should only be rewarded");
+}
+
+#[test]
+fn digest_binds_header_fields() {
+    let key_a = Keypair::from_seed([1u8; 32]);
+    let key_b = Keypair::from_seed([3u8; 32]);
+    let text = || Content::Text(TextBody { canonical_text: "same words".into() });
+    let at = Timestamp(1_700_000_000_000);
+
+    // same content from two senders -> two different digests
+    let from_a = assemble_message(&key_a, zero_digest(), text(), at);
+    let from_b = assemble_message(&key_b, zero_digest(), text(), at);
+    assert_ne!(from_a.digest, from_b.digest, "sender must be part of the digest");
+
+    // a relayer re-parents A's message
+    let mut reparented = from_a.clone();
+    reparented.parent = Digest([5u8; 32]);
+    assert_eq!(verify_digest(&reparented), Err(VerifyError::BadDigest), "parent must be part of the digest");
+
+    // a relayer re-timestamps A's message
+    let mut retimed = from_a.clone();
+    retimed.timestamp = Timestamp(at.0 + 1);
+    assert_eq!(verify_digest(&retimed), Err(VerifyError::BadDigest), "timestamp must be part of the digest");
+
+    // and the untouched message still verifies
+    assert_eq!(verify_digest(&from_a), Ok(()));
+    assert_eq!(
+        from_a.digest,
+        message_digest(&from_a.sender, &from_a.parent, &from_a.timestamp, &from_a.content)
+    );
+}
@@ -0,0 +1,170 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use collapse_messenger::content::Content;
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::node::NodeMessenger;
+use collapse_messenger::types::zero_digest;
+use collapse_messenger::phi::Evidence;
+use collapse_messenger::store::MemStore;
+use collapse_messenger::transport_tcp::{TcpTransport, MAX_FRAME_LEN, MAX_INBOUND_CONNECTIONS};
+
+/// Poll `node` until its inbox reaches `len` or a few seconds pass.
+fn poll_until(node: &mut NodeMessenger, len: usize) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while node.inbox.len() < len && Instant::now() < deadline {
+        node.poll();
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn tcp_flow_demo() {
+    let key_a = Keypair::generate();
+    let key_b = Keypair::generate();
+    let key_c = Keypair::generate();
+
+    let mut ta = TcpTransport::bind("127.0.0.1:0").unwrap();
+    let mut tb = TcpTransport::bind("127.0.0.1:0").unwrap();
+    let mut tc = TcpTransport::bind("127.0.0.1:0").unwrap();
+    let (addr_a, addr_b, addr_c) = (ta.local_addr(), tb.local_addr(), tc.local_addr());
+
+    ta.add_peer(key_b.public.clone(), addr_b);
+    ta.add_peer(key_c.public.clone(), addr_c);
+    tb.add_peer(key_a.public.clone(), addr_a);
+    tb.add_peer(key_c.public.clone(), addr_c);
+    tc.add_peer(key_a.public.clone(), addr_a);
+    tc.add_peer(key_b.public.clone(), addr_b);
+
+    let mut a = NodeMessenger::new(key_a.clone(), ta);
+    let mut b = NodeMessenger::new(key_b, tb);
+    let mut c = NodeMessenger::new(key_c.clone(), tc);
+
+    // 1. A's root reaches B and C over sockets
+    a.send(zero_digest(), Evidence::DraftText { raw: "hello over tcp".into() }).unwrap();
+    poll_until(&mut b, 1);
+    poll_until(&mut c, 1);
+    assert_eq!(b.inbox.len(), 1);
+    assert_eq!(c.inbox.len(), 1);
+    let root = a.inbox[0].digest.clone();
+    assert_eq!(b.inbox[0].digest, root);
+
+    // 2. B replies, A and C accept the reply
+    b.send(root.clone(), Evidence::DraftText { raw: "reply over tcp".into() }).unwrap();
+    poll_until(&mut a, 2);
+    poll_until(&mut c, 2);
+    assert_eq!(a.inbox.len(), 2);
+    assert!(a.rep.get(&b.id) > 0.5);
+
+    // 3. C restarts on the same port with an empty inbox; A's cached
+    //    connection is dead, so A reconnects, and C heals the thread
+    drop(c);
+    let mut tc = TcpTransport::bind(addr_c).unwrap();
+    tc.add_peer(key_a.public.clone(), addr_a);
+    tc.add_peer(b.id.clone(), addr_b);
+    let mut c = NodeMessenger::new(key_c, tc);
+
+    let reply = a.inbox[1].digest.clone();
+    a.send(reply, Evidence::DraftText { raw: "after restart".into() }).unwrap();
+
+    // C sees an orphan and asks A and B for history
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while c.inbox.len() < 3 && Instant::now() < deadline {
+        a.poll();
+        b.poll();
+        c.poll();
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    println!("C inbox len after restart = {}", c.inbox.len());
+    assert_eq!(c.inbox.len(), 3, "root + reply + new message after heal");
+    assert_eq!(c.inbox[0].digest, root);
+    assert_eq!(c.pending_len(), 0);
+}
+
+#[test]
+fn closed_inbound_connections_are_released() {
+    let t = TcpTransport::bind("127.0.0.1:0").unwrap();
+    let clients: Vec<TcpStream> = (0..3).map(|_| TcpStream::connect(t.local_addr()).unwrap()).collect();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while t.inbound_connections() < 3 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(t.inbound_connections(), 3);
+
+    // peers hang up; their reader threads exit and drop the entries
+    drop(clients);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while t.inbound_connections() > 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(t.inbound_connections(), 0);
+}
+
+#[test]
+fn retina_and_attestations_cross_tcp() {
+    let key_a = Keypair::generate();
+    let key_b = Keypair::generate();
+    let mut ta = TcpTransport::bind("127.0.0.1:0").unwrap();
+    let mut tb = TcpTransport::bind("127.0.0.1:0").unwrap();
+    ta.add_peer(key_b.public.clone(), tb.local_addr());
+    tb.add_peer(key_a.public.clone(), ta.local_addr());
+    let mut a = NodeMessenger::with_store(key_a, Box::new(ta), Box::new(MemStore::new()));
+    let mut b = NodeMessenger::with_store(key_b, Box::new(tb), Box::new(MemStore::new()));
+
+    // a retina capture carries hashed floats in its body and certificate
+    a.send(
+        zero_digest(),
+        Evidence::RawRetinaCapture {
+            samples: (0..64).map(|i| ((i % 8) as f32 / 7.3, (i / 8) as f32 / 6.9, (i as f32).sin())).collect(),
+            lambda: 1e-3,
+            foveation_cfg: (0.3, 0.5, 0.5),
+            basis_cfg: (8, 8),
+            cert_seed: 11,
+        },
+    )
+    .unwrap();
+    poll_until(&mut b, 1);
+    assert_eq!(b.inbox.len(), 1, "B accepts the retina message");
+    assert!(matches!(b.inbox[0].content, Content::Retina(_)));
+    assert!(b.rep.local(&a.id) > 0.5);
+
+    // an attestation carries hashed float scores
+    b.rep.set(&Keypair::generate().public, 0.123_456_789_012_345_6);
+    let attestation = b.publish_attestation();
+    poll_until(&mut a, 2);
+    assert_eq!(a.inbox.len(), 2, "A accepts the attestation");
+    assert_eq!(a.inbox[1].digest, attestation);
+    assert!(a.rep.attestation(&b.id).is_some());
+}
+
+#[test]
+fn inbound_connections_are_bounded() {
+    let t = TcpTransport::bind("127.0.0.1:0").unwrap();
+    let wait_for = |n: usize| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while t.inbound_connections() != n && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(t.inbound_connections(), n);
+    };
+
+    // connections beyond the cap are closed on accept
+    let clients: Vec<TcpStream> = (0..MAX_INBOUND_CONNECTIONS + 4)
+        .map(|_| TcpStream::connect(t.local_addr()).unwrap())
+        .collect();
+    wait_for(MAX_INBOUND_CONNECTIONS);
+    drop(clients);
+    wait_for(0);
+
+    // an oversized length prefix is refused before anything is allocated
+    let mut client = TcpStream::connect(t.local_addr()).unwrap();
+    wait_for(1);
+    client.write_all(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes()).unwrap();
+    wait_for(0);
+    let mut rest = Vec::new();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(client.read_to_end(&mut rest).unwrap(), 0, "server hung up");
+}
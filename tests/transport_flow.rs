@@ -1,10 +1,13 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
+use collapse_messenger::keys::Keypair;
 use collapse_messenger::node::NodeMessenger;
-use collapse_messenger::types::{PubKey, Digest, zero_digest};
+use collapse_messenger::types::{PubKey, Digest, zero_digest, Timestamp, now_timestamp};
 use collapse_messenger::phi::Evidence;
+use collapse_messenger::transport::Transport;
 use collapse_messenger::transport_mem::MemoryTransport;
+use collapse_messenger::wire::Frame;
 use collapse_messenger::content::Content;
 
 #[test]
@@ -13,24 +16,24 @@ fn transport_flow_demo() {
     let bus = Rc::new(RefCell::new(MemoryTransport::new()));
 
     // three nodes on that bus
-    let mut a = NodeMessenger::new(PubKey("A".into()), bus.clone());
-    let mut b = NodeMessenger::new(PubKey("B".into()), bus.clone());
-    let mut c = NodeMessenger::new(PubKey("C".into()), bus.clone());
+    let mut a = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let mut b = NodeMessenger::new(Keypair::generate(), bus.clone());
+    let mut c = NodeMessenger::new(Keypair::generate(), bus.clone());
 
     // they "know" each other, but that's social; bus already registered them
-    a.add_peer(PubKey("B".into()));
-    a.add_peer(PubKey("C".into()));
-    b.add_peer(PubKey("A".into()));
-    b.add_peer(PubKey("C".into()));
-    c.add_peer(PubKey("A".into()));
-    c.add_peer(PubKey("B".into()));
+    a.add_peer(b.id.clone());
+    a.add_peer(c.id.clone());
+    b.add_peer(a.id.clone());
+    b.add_peer(c.id.clone());
+    c.add_peer(a.id.clone());
+    c.add_peer(b.id.clone());
 
     // 1. A sends root text
     let root_parent = zero_digest();
     a.send(
         root_parent,
         Evidence::DraftText { raw: "hi from A".into() }
-    );
+    ).unwrap();
 
     // at this point, A has accepted its own msg, and bus broadcast queued copies.
     // b and c haven't polled yet, so they haven't processed it.
@@ -53,7 +56,7 @@ fn transport_flow_demo() {
             basis_cfg: (16, 16),
             cert_seed: 99,
         }
-    );
+    ).unwrap();
 
     // Now bus has B's retinal message queued for others.
     a.poll();
@@ -64,19 +67,23 @@ fn transport_flow_demo() {
     c.send(
         bogus_parent,
         Evidence::DraftText { raw: "i am chaos".into() }
-    );
+    ).unwrap();
 
     // That message broadcasts out too, but it's causality-invalid.
     a.poll();
     b.poll();
 
+    // Nobody ever supplies the bogus parent, so the orphan times out.
+    a.expire_pending(Timestamp(now_timestamp().0 + a.pending_timeout_ms + 1));
+
     // 4. Check A's perspective
     println!("A inbox len = {}", a.inbox.len());
     assert!(a.inbox.len() >= 2, "A should have its own text + B's retina");
 
-    let rep_a = a.rep.get(&PubKey("A".into()));
-    let rep_b = a.rep.get(&PubKey("B".into()));
-    let rep_c = a.rep.get(&PubKey("C".into()));
+    let rep_a = a.rep.get(&a.id);
+    // as of B's last reward: the score fades toward neutral afterwards
+    let rep_b = a.rep.saved_scores()[&b.id].score;
+    let rep_c = a.rep.get(&c.id);
     println!("A rep(A) = {}", rep_a);
     println!("A rep(B) = {}", rep_b);
     println!("A rep(C) = {}", rep_c);
@@ -91,3 +98,47 @@ fn transport_flow_demo() {
     println!("A saw retina over transport? {}", saw_retina);
     assert!(saw_retina, "RetinaBody should survive transport and still be accepted");
 }
+
+/// A transport defined outside the crate: records what the node does.
+#[derive(Default)]
+struct RecordingTransport {
+    registered: Vec<PubKey>,
+    sent: Vec<Frame>,
+}
+
+impl Transport for RecordingTransport {
+    fn register(&mut self, me: &PubKey) {
+        self.registered.push(me.clone());
+    }
+
+    fn send_to(&mut self, _to: &PubKey, frame: &Frame) {
+        self.sent.push(frame.clone());
+    }
+
+    fn broadcast(&mut self, _from: &PubKey, frame: &Frame) {
+        self.sent.push(frame.clone());
+    }
+
+    fn drain_inbound(&mut self, _me: &PubKey) -> Vec<Frame> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn custom_transport_plugs_in() {
+    let rec = Rc::new(RefCell::new(RecordingTransport::default()));
+    let mut a = NodeMessenger::new(Keypair::generate(), rec.clone());
+
+    // construction registered the node on its transport
+    assert_eq!(rec.borrow().registered, vec![a.id.clone()]);
+
+    a.send(zero_digest(), Evidence::DraftText { raw: "over a custom transport".into() }).unwrap();
+    a.poll();
+
+    let rec = rec.borrow();
+    assert_eq!(rec.sent.len(), 1);
+    match &rec.sent[0] {
+        Frame::Message(m) => assert_eq!(m.digest, a.inbox[0].digest),
+        other => panic!("expected a message frame, got {:?}", other),
+    }
+}
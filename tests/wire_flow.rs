@@ -0,0 +1,59 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use collapse_messenger::content::{AttestationBody, Content, Message};
+use collapse_messenger::keys::Keypair;
+use collapse_messenger::phi::{assemble_message, collapse_evidence, Evidence};
+use collapse_messenger::store::MemStore;
+use collapse_messenger::types::{now_timestamp, zero_digest};
+use collapse_messenger::verify::{verify_content, verify_digest};
+use collapse_messenger::wire::{decode_frame, encode_frame, Frame};
+
+fn round_trip(msg: &Message) -> Message {
+    match decode_frame(&encode_frame(&Frame::Message(msg.clone()))) {
+        Some(Frame::Message(m)) => m,
+        other => panic!("expected a message frame, got {:?}", other),
+    }
+}
+
+#[test]
+fn floats_survive_the_wire() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let key = Keypair::generate();
+
+    // 1. retina messages: coefficients and certificate are hashed bit
+    //    for bit, so a lossy float round trip breaks both
+    for seed in 0..20u64 {
+        let samples = (0..64).map(|_| (rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>())).collect();
+        let content = collapse_evidence(
+            Evidence::RawRetinaCapture {
+                samples,
+                lambda: 1e-3,
+                foveation_cfg: (0.3, 0.5, 0.5),
+                basis_cfg: (8, 8),
+                cert_seed: seed,
+            },
+            &mut MemStore::new(),
+        )
+        .unwrap();
+        let msg = assemble_message(&key, zero_digest(), content, now_timestamp());
+        let back = round_trip(&msg);
+        assert_eq!(verify_digest(&back), Ok(()));
+        assert_eq!(verify_content(&back), Ok(()));
+    }
+
+    // 2. attestation scores are hashed the same way
+    let mut scores: Vec<_> = (0..32u8)
+        .map(|i| (Keypair::from_seed([i; 32]).public, rng.gen::<f64>()))
+        .collect();
+    scores.sort_by_key(|(who, _)| who.0);
+    let msg = assemble_message(
+        &key,
+        zero_digest(),
+        Content::Attestation(AttestationBody { scores }),
+        now_timestamp(),
+    );
+    let back = round_trip(&msg);
+    assert_eq!(verify_digest(&back), Ok(()));
+    assert_eq!(verify_content(&back), Ok(()));
+}